@@ -0,0 +1,114 @@
+use crate::{config, error::Error, hooks};
+
+use log::info;
+use std::{
+    env,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// `actions_dir` is where todo.sh add-ons are kept, looked for in the same
+/// order todo.sh looks: `$TODO_ACTIONS_DIR`, then `$TODO_DIR/.todo.actions.d`,
+/// then `$TODO_DIR/actions`, then `~/.todo.actions.d`, whichever exists first
+fn actions_dir() -> Option<PathBuf> {
+    let todo_dir = config::todo_dir();
+    let home = env::var_os("HOME").map(|h| PathBuf::from(h).join(".todo.actions.d"));
+    env::var_os("TODO_ACTIONS_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(vec![todo_dir.join(".todo.actions.d"), todo_dir.join("actions")])
+        .chain(home)
+        .find(|dir| dir.is_dir())
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+/// `find` is the executable for the add-on `action`, which is either
+/// `action/action` or just `action` in the actions directory
+fn find(action: &str) -> Option<PathBuf> {
+    // an action is a plain name, not a path out of the directory
+    if action.is_empty() || action.contains('/') {
+        return None;
+    }
+    let dir = actions_dir()?;
+    [dir.join(action).join(action), dir.join(action)]
+        .iter()
+        .find(|path| is_executable(path))
+        .cloned()
+}
+
+/// `exists` is whether there's an add-on for `action`
+pub fn exists(action: &str) -> bool {
+    find(action).is_some()
+}
+
+/// `command` sets up an add-on to run the way todo.sh runs it, with the name
+/// of the action ahead of its arguments and the paths of the files exported
+fn command(action: &str, args: &[&str]) -> Result<Command, Error> {
+    let path = find(action)
+        .ok_or_else(|| format!("TODO: no action or add-on named {}", action))?;
+    let dir = config::todo_dir();
+    let exe = env::current_exe()?;
+
+    let mut command = Command::new(path);
+    command
+        .arg(action)
+        .args(args)
+        .env("TODO_DIR", &dir)
+        .env("TODO_FILE", dir.join(config::TODO_FILE))
+        .env("DONE_FILE", dir.join(config::DONE_FILE))
+        .env("TODO_FULL_SH", &exe)
+        .env("TODO_SH", &exe);
+    Ok(command)
+}
+
+fn program(command: &Command) -> PathBuf {
+    PathBuf::from(command.get_program())
+}
+
+/// `run` runs an add-on attached to the terminal, returning its exit code
+pub fn run(action: &str, args: &[&str]) -> Result<i32, Error> {
+    let mut command = command(action, args)?;
+    let status = command.status().map_err(Error::io(&program(&command)))?;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// `run_captured` runs the add-on named by the first word of `line` from the
+/// interface, where its output can't be shown as is. What's returned is a
+/// line for the status bar.
+pub fn run_captured(line: &str) -> Result<String, Error> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((action, args)) = words.split_first() else {
+        return Ok(String::new());
+    };
+
+    let mut command = command(action, args)?;
+    info!("running the {} add-on: {:?}", action, command);
+    // with nothing on its stdin and a time limit, so one waiting on input
+    // or hanging can't hold up the interface
+    let Some(output) = hooks::output_within(&mut command, None, hooks::TIMEOUT)
+        .map_err(Error::io(&program(&command)))?
+    else {
+        return Ok(format!("{} failed: {}", action, hooks::killed(hooks::TIMEOUT)));
+    };
+    let last_line = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .rev()
+            .find(|l| !l.trim().is_empty())
+            .map(str::to_owned)
+    };
+
+    Ok(if output.status.success() {
+        last_line(&output.stdout).unwrap_or_else(|| format!("{} finished", action))
+    } else {
+        let reason = last_line(&output.stderr)
+            .or_else(|| last_line(&output.stdout))
+            .unwrap_or_else(|| output.status.to_string());
+        format!("{} failed: {}", action, reason)
+    })
+}
@@ -0,0 +1,272 @@
+use crate::{
+    app::{draw_status_bar, draw_timer, task_key, BlockState, Prompt, View, ViewKind},
+    event::{Event, Handler as EventHandler},
+    runner::Action,
+    timer::Timer,
+    todo::{
+        date::{self, Clock},
+        ParsedLine,
+    },
+};
+
+use chrono::NaiveDate;
+use termion::event::Key;
+use tui::{
+    backend::Backend,
+    layout::{
+        Constraint::{Length, Min},
+        Direction, Layout,
+    },
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Frame,
+};
+
+/// how many days ahead the upcoming section looks
+const UPCOMING_DAYS: i64 = 7;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Section {
+    Overdue,
+    DueToday,
+    Available,
+    Upcoming,
+}
+
+impl Section {
+    fn title(self) -> &'static str {
+        match self {
+            Section::Overdue => "Overdue",
+            Section::DueToday => "Due today",
+            Section::Available => "Available today",
+            Section::Upcoming => "Next 7 days",
+        }
+    }
+}
+
+/// `Entry` is a task listed in one of the agenda sections, along with the
+/// date which put it there
+struct Entry {
+    item: usize,
+    section: Section,
+    date: NaiveDate,
+    due: bool,
+}
+
+pub struct AgendaView<'a> {
+    state: &'a mut BlockState,
+    status: &'a mut Option<String>,
+    today: &'a mut NaiveDate,
+    items: Vec<ParsedLine<'a>>,
+    entries: Vec<Entry>,
+    prompt: Option<Prompt>,
+    timer: Option<&'a Timer>,
+    clock: &'a dyn Clock,
+}
+
+impl<'a> AgendaView<'a> {
+    pub fn new(
+        state: &'a mut BlockState,
+        status: &'a mut Option<String>,
+        today: &'a mut NaiveDate,
+        items: Vec<ParsedLine<'a>>,
+        timer: Option<&'a Timer>,
+        clock: &'a dyn Clock,
+    ) -> Self {
+        let entries = entries(&items, *today);
+        state.reset(entries.len());
+
+        Self {
+            state,
+            status,
+            today,
+            items,
+            entries,
+            prompt: None,
+            timer,
+            clock,
+        }
+    }
+
+    fn selected_task(&self) -> Option<&ParsedLine<'a>> {
+        self.state
+            .pos
+            .selected()
+            .and_then(|i| self.entries.get(i))
+            .map(|e| &self.items[e.item])
+    }
+
+    fn entry_line(&self, entry: &Entry) -> Spans<'_> {
+        let days = (entry.date - *self.today).num_days();
+        let (note, style) = match entry.section {
+            Section::Overdue => (
+                format!("{:>10} ", date::span(days)),
+                Style::default().fg(Color::Red),
+            ),
+            Section::DueToday => ("     today ".to_owned(), Style::default().fg(Color::Yellow)),
+            Section::Available => ("    starts ".to_owned(), Style::default().fg(Color::Blue)),
+            Section::Upcoming => (
+                format!(
+                    "{:>10} ",
+                    format!(
+                        "{} {}",
+                        if entry.due { "due" } else { "starts" },
+                        entry.date.format("%a")
+                    )
+                ),
+                Style::default().fg(if entry.due {
+                    Color::DarkGray
+                } else {
+                    Color::Blue
+                }),
+            ),
+        };
+
+        Spans::from(vec![
+            Span::styled(note, style),
+            Span::styled(
+                &self.items[entry.item].body[..],
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        ])
+    }
+}
+
+/// `entries` sorts the incomplete tasks into the agenda sections. A task due
+/// or available today is listed once, in its most pressing section.
+fn entries(items: &[ParsedLine], today: NaiveDate) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        if item.complete {
+            continue;
+        }
+
+        let due = item.due_date.and_then(date::from_iso);
+        let threshold = item.threshold_date.and_then(date::from_iso);
+        let ahead = |d: NaiveDate| (1..=UPCOMING_DAYS).contains(&(d - today).num_days());
+
+        let mut push = |section, date, due| {
+            entries.push(Entry {
+                item: i,
+                section,
+                date,
+                due,
+            });
+        };
+
+        match (due, threshold) {
+            (Some(d), _) if d < today => push(Section::Overdue, d, true),
+            (Some(d), _) if d == today => push(Section::DueToday, d, true),
+            (_, Some(t)) if t == today => push(Section::Available, t, false),
+            (Some(d), _) if ahead(d) => push(Section::Upcoming, d, true),
+            (_, Some(t)) if ahead(t) => push(Section::Upcoming, t, false),
+            _ => {}
+        }
+    }
+
+    entries.sort_by_key(|e| (e.section as u8, e.date));
+    entries
+}
+
+impl<'a> View for AgendaView<'a> {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Min(0), Length(1)].as_ref())
+            .split(f.size());
+
+        let mut list_items = Vec::new();
+        let mut rows = Vec::with_capacity(self.entries.len());
+        let mut section = None;
+        for entry in &self.entries {
+            if section != Some(entry.section) {
+                section = Some(entry.section);
+                let count = self
+                    .entries
+                    .iter()
+                    .filter(|e| e.section == entry.section)
+                    .count();
+                list_items.push(ListItem::new(Spans::from(Span::styled(
+                    format!("{} ({})", entry.section.title(), count),
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::UNDERLINED),
+                ))));
+            }
+            rows.push(list_items.len());
+            list_items.push(ListItem::new(self.entry_line(entry)));
+        }
+
+        if list_items.is_empty() {
+            list_items.push(ListItem::new(Span::styled(
+                "nothing on the agenda",
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+
+        // headers aren't selectable, so the selected entry is mapped onto
+        // its row in the rendered list
+        let mut pos = ListState::default();
+        pos.select(self.state.pos.selected().and_then(|i| rows.get(i).copied()));
+
+        let list = List::new(list_items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(self.today.format("Agenda %a %-d %b").to_string()),
+            )
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("*");
+
+        f.render_stateful_widget(list, chunks[0], &mut pos);
+        let status_chunk = draw_timer(f, chunks[1], self.timer, self.clock.now());
+        draw_status_bar(f, status_chunk, self.prompt.as_ref(), self.status.as_ref());
+    }
+}
+
+impl<'a> EventHandler<Key> for AgendaView<'a> {
+    fn handle(&mut self, event: Event<Key>) -> Option<Action> {
+        match event {
+            Event::Input(key) if self.prompt.is_some() => Prompt::handle(&mut self.prompt, key),
+            Event::Input(key) => {
+                *self.status = None;
+                match key {
+                    Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => Some(Action::Exit),
+                    Key::Char('j') | Key::Down => {
+                        self.state.next();
+                        None
+                    }
+                    Key::Char('k') | Key::Up => {
+                        self.state.previous();
+                        None
+                    }
+                    Key::Char('w') => Some(Action::Write),
+                    Key::Char('W') => Some(Action::ForceWrite),
+                    Key::Char('r') => Some(Action::Reload),
+                    Key::Char('c') => Some(Action::SwitchView(ViewKind::Calendar)),
+                    Key::Char('g') | Key::Esc => Some(Action::SwitchView(ViewKind::Main)),
+                    key => {
+                        let task = self.selected_task();
+                        let targets = task.map(|t| t.index).into_iter().collect();
+                        task_key(key, task, targets).and_then(|c| c.apply(&mut self.prompt))
+                    }
+                }
+            }
+            Event::Tick => {
+                let today = self.clock.today();
+                if today == *self.today {
+                    return None;
+                }
+                // sections depend on the day, so they are rebuilt on rollover
+                *self.today = today;
+                Some(Action::Refresh)
+            }
+        }
+    }
+}
@@ -2,18 +2,73 @@ use crate::{
     event::{Event, Handler as EventHandler},
     runner::Action,
 };
-use crate::{filters::Filters, todo::ParsedLine};
+use crate::{
+    backups::BackupsState,
+    board::BoardState,
+    calendar::CalendarState,
+    config::{DateConfig, SidebarConfig},
+    filters::{Filters, Kind},
+    pomodoro::Session,
+    scripts::Scripts,
+    sidebar::{Entry, Order},
+    timer::Timer,
+    todo::{
+        date::{self, Clock},
+        deps::Deps,
+        tree::{Row, Tree},
+        ListRep, ListSnapshot, ParsedLine,
+    },
+};
 
+use chrono::{NaiveDate, NaiveDateTime};
+use std::{collections::BTreeSet, convert::TryFrom};
 use termion::event::Key;
 use tui::{
     backend::Backend,
-    layout::{Constraint::Percentage, Direction, Layout, Rect},
+    layout::{
+        Constraint::{Length, Min, Percentage},
+        Direction, Layout, Rect,
+    },
     style::{Color, Modifier, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, List, ListItem, ListState},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
     Frame,
 };
 
+/// `View` is a screen which can be drawn and handle events until it produces
+/// an action for the runner
+pub trait View: EventHandler<Key> {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>);
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ViewKind {
+    Main,
+    Calendar,
+    Agenda,
+    Board,
+    Pomodoro,
+    Stats,
+    Projects,
+    Log,
+    Backups,
+}
+
+impl ViewKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "main" => Some(ViewKind::Main),
+            "calendar" => Some(ViewKind::Calendar),
+            "agenda" => Some(ViewKind::Agenda),
+            "board" => Some(ViewKind::Board),
+            "stats" => Some(ViewKind::Stats),
+            "projects" => Some(ViewKind::Projects),
+            "log" => Some(ViewKind::Log),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ActiveList {
     Tasks,
@@ -29,6 +84,15 @@ impl ActiveList {
             ActiveList::Tags => "Tags",
         }
     }
+
+    /// `filter` is the kind of filter listed, which the tasks aren't
+    pub fn filter(self) -> Option<Kind> {
+        match self {
+            ActiveList::Tasks => None,
+            ActiveList::Contexts => Some(Kind::Contexts),
+            ActiveList::Tags => Some(Kind::Tags),
+        }
+    }
 }
 
 pub struct BlockState {
@@ -42,13 +106,20 @@ impl BlockState {
     }
 
     pub fn next(&mut self) {
+        // there's nothing to select in an empty list
+        if self.len == 0 {
+            return;
+        }
         self.pos.select(Some(match self.pos.selected() {
-            Some(i) if i < self.len => i + 1,
+            Some(i) if i + 1 < self.len => i + 1,
             _ => 0,
         }))
     }
 
     pub fn previous(&mut self) {
+        if self.len == 0 {
+            return;
+        }
         self.pos.select(Some(match self.pos.selected() {
             Some(i) if i == 0 => self.len - 1,
             Some(i) => i - 1,
@@ -56,6 +127,10 @@ impl BlockState {
         }))
     }
 
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
     pub fn reset(&mut self, len: usize) {
         if self.len != len {
             self.len = len;
@@ -68,20 +143,270 @@ impl BlockState {
     }
 }
 
+/// `PromptKind` is what a line typed into the prompt will be used for
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PromptKind {
+    Add,
+    AddChild(usize),
+    Edit(usize),
+    Due(usize),
+    Snooze(usize),
+    Defer(Vec<usize>),
+    /// a context or project and the number of lines mentioning it
+    Rename(String, usize),
+    Merge(String, usize),
+    Strip(String, usize),
+    Addon,
+    /// a script command, run with the selected task if there is one
+    Script(Option<usize>),
+}
+
+/// presets cycled through with tab in the defer prompt
+const DEFER_PRESETS: [&str; 3] = ["+1d", "next-week", "next-mon"];
+
+impl PromptKind {
+    fn title(&self) -> String {
+        match self {
+            PromptKind::Add => "Add".to_owned(),
+            PromptKind::AddChild(_) => "Add subtask".to_owned(),
+            PromptKind::Edit(_) => "Edit".to_owned(),
+            PromptKind::Due(_) => "Due".to_owned(),
+            PromptKind::Snooze(_) => "Snooze until".to_owned(),
+            PromptKind::Defer(_) => "Defer (tab for presets, empty to clear)".to_owned(),
+            PromptKind::Rename(name, lines) => format!("Rename {} on {} lines to", name, lines),
+            PromptKind::Merge(name, lines) => format!("Merge {} on {} lines into", name, lines),
+            PromptKind::Strip(name, lines) => format!("Strip {} from {} lines? (y/n)", name, lines),
+            PromptKind::Addon => "Add-on".to_owned(),
+            PromptKind::Script(_) => "Script command".to_owned(),
+        }
+    }
+
+    fn action(self, input: String) -> Action {
+        match self {
+            PromptKind::Add => Action::Add(input),
+            PromptKind::AddChild(i) => Action::AddChild(i, input),
+            PromptKind::Edit(i) => Action::Edit(i, input),
+            PromptKind::Due(i) => Action::SetDue(i, input),
+            PromptKind::Snooze(i) => Action::Snooze(i, input),
+            PromptKind::Defer(indices) => Action::Defer(indices, input),
+            PromptKind::Rename(name, _) => Action::Retag(name, Some(input)),
+            PromptKind::Merge(name, _) => Action::Merge(name, input),
+            PromptKind::Strip(name, _) if input.trim() == "y" => Action::Retag(name, None),
+            PromptKind::Strip(..) => Action::Refresh,
+            PromptKind::Addon => Action::RunAddon(input),
+            PromptKind::Script(selected) => Action::RunScript(selected, input),
+        }
+    }
+}
+
+pub struct Prompt {
+    kind: PromptKind,
+    input: String,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind, input: String) -> Self {
+        Self { kind, input }
+    }
+
+    /// `open` starts a prompt for `kind`, prefilled with the task line when
+    /// it's being edited
+    pub fn open(kind: PromptKind, task: Option<&ParsedLine>) -> Self {
+        let input = match (&kind, task) {
+            (PromptKind::Edit(_), Some(t)) => t.raw.to_owned(),
+            (PromptKind::Rename(name, _), _) => name.clone(),
+            _ => String::new(),
+        };
+        Self::new(kind, input)
+    }
+
+    /// `handle` feeds a key to the open prompt in `slot`, closing it and
+    /// returning its action once submitted
+    pub fn handle(slot: &mut Option<Prompt>, key: Key) -> Option<Action> {
+        let prompt = slot.as_mut()?;
+        match key {
+            Key::Char('\n') => {
+                let prompt = slot.take()?;
+                return Some(prompt.kind.action(prompt.input));
+            }
+            Key::Esc | Key::Ctrl('c') => *slot = None,
+            Key::Char('\t') if matches!(prompt.kind, PromptKind::Defer(_)) => {
+                let next = DEFER_PRESETS
+                    .iter()
+                    .position(|&p| p == prompt.input)
+                    .map_or(0, |i| (i + 1) % DEFER_PRESETS.len());
+                DEFER_PRESETS[next].clone_into(&mut prompt.input);
+            }
+            Key::Backspace => {
+                prompt.input.pop();
+            }
+            Key::Char(c) => prompt.input.push(c),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// `Command` is the result of a key acting on a task, which either runs an
+/// action straight away or first asks for input
+pub enum Command {
+    Run(Action),
+    Ask(Prompt),
+}
+
+impl Command {
+    /// `apply` opens the prompt in `slot` or hands back the action to run
+    pub fn apply(self, slot: &mut Option<Prompt>) -> Option<Action> {
+        match self {
+            Command::Run(action) => Some(action),
+            Command::Ask(prompt) => {
+                *slot = Some(prompt);
+                None
+            }
+        }
+    }
+}
+
+/// `task_key` maps the keys acting on tasks which are shared by every view
+/// listing them. `task` is the selected task and `targets` the file indices
+/// a bulk action applies to.
+pub fn task_key(key: Key, task: Option<&ParsedLine>, targets: Vec<usize>) -> Option<Command> {
+    let kind = match (key, task) {
+        (Key::Char('a'), _) => PromptKind::Add,
+        (Key::Char(' '), Some(t)) => return Some(Command::Run(Action::Complete(t.index))),
+        (Key::Char('D'), Some(t)) => return Some(Command::Run(Action::Delete(t.index))),
+        (Key::Char('e'), Some(t)) => PromptKind::Edit(t.index),
+        (Key::Char('d'), Some(t)) => PromptKind::Due(t.index),
+        (Key::Char('s'), Some(t)) => PromptKind::Snooze(t.index),
+        (Key::Char('f'), _) if !targets.is_empty() => PromptKind::Defer(targets),
+        (Key::Char('T'), Some(t)) => return Some(Command::Run(Action::ToggleTimer(t.index))),
+        _ => return None,
+    };
+    Some(Command::Ask(Prompt::open(kind, task)))
+}
+
+/// `draw_timer` renders the running timer at the right of the status bar,
+/// handing back what's left of `chunk` for the rest of it
+pub fn draw_timer<B: Backend>(
+    f: &mut Frame<'_, B>,
+    chunk: Rect,
+    timer: Option<&Timer>,
+    now: NaiveDateTime,
+) -> Rect {
+    let Some(timer) = timer else {
+        return chunk;
+    };
+
+    let text = timer.describe(now);
+    let width = u16::try_from(text.chars().count())
+        .unwrap_or(chunk.width)
+        .min(chunk.width);
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Min(0), Length(width)].as_ref())
+        .split(chunk);
+
+    f.render_widget(
+        Paragraph::new(Span::styled(text, Style::default().fg(Color::Magenta))),
+        chunks[1],
+    );
+    chunks[0]
+}
+
+/// `draw_status_bar` renders the open prompt, or otherwise the last status
+/// message, on a single line
+pub fn draw_status_bar<B: Backend>(
+    f: &mut Frame<'_, B>,
+    chunk: Rect,
+    prompt: Option<&Prompt>,
+    status: Option<&String>,
+) {
+    let line = match (prompt, status) {
+        (Some(prompt), _) => Spans::from(vec![
+            Span::styled(
+                format!("{}: ", prompt.kind.title()),
+                Style::default().fg(Color::Green),
+            ),
+            Span::raw(&prompt.input[..]),
+            Span::styled("_", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+        ]),
+        (None, Some(status)) => Spans::from(Span::styled(
+            &status[..],
+            Style::default().fg(Color::Yellow),
+        )),
+        (None, None) => Spans::default(),
+    };
+
+    f.render_widget(Paragraph::new(line), chunk);
+}
+
 pub struct State {
     pub tasks: BlockState,
     pub contexts: BlockState,
     pub tags: BlockState,
     pub active_list: ActiveList,
+    pub status: Option<String>,
+    pub relative_dates: bool,
+    pub today: NaiveDate,
+    pub marked: BTreeSet<usize>,
+    pub show_deferred: bool,
+    /// ids of the tasks whose subtasks are hidden
+    pub folded: BTreeSet<String>,
+    /// leave blocked tasks out of the main view
+    pub actionable_only: bool,
+    pub sidebar: SidebarConfig,
+    /// sidebar nodes whose nested contexts or tags are hidden
+    pub collapsed: BTreeSet<String>,
+    pub timer: Option<Timer>,
+    pub pomodoro: Option<Session>,
+    pub scripts: Scripts,
+    /// the dependency warnings last reported in the status bar
+    pub warnings: Vec<String>,
+    /// snapshots taken before each batch edit, described for the status bar
+    pub undo: Vec<(String, ListSnapshot)>,
+    pub view: ViewKind,
+    pub calendar: CalendarState,
+    pub agenda: BlockState,
+    pub board: BoardState,
+    pub projects: BlockState,
+    pub log: BlockState,
+    pub backups: BackupsState,
 }
 
 impl State {
-    pub fn new(tasklen: usize, ctxlen: usize, taglen: usize) -> Self {
+    pub fn new(
+        tasklen: usize,
+        ctxlen: usize,
+        taglen: usize,
+        relative_dates: bool,
+        today: NaiveDate,
+    ) -> Self {
         Self {
             tasks: BlockState::new(ListState::default(), tasklen),
             contexts: BlockState::new(ListState::default(), ctxlen),
             tags: BlockState::new(ListState::default(), taglen),
             active_list: ActiveList::Tasks,
+            status: None,
+            relative_dates,
+            today,
+            marked: BTreeSet::new(),
+            show_deferred: false,
+            folded: BTreeSet::new(),
+            actionable_only: false,
+            sidebar: SidebarConfig::default(),
+            collapsed: BTreeSet::new(),
+            timer: None,
+            pomodoro: None,
+            scripts: Scripts::new(today),
+            warnings: Vec::new(),
+            undo: Vec::new(),
+            view: ViewKind::Main,
+            calendar: CalendarState::new(today),
+            agenda: BlockState::new(ListState::default(), 0),
+            board: BoardState::new(),
+            projects: BlockState::new(ListState::default(), 0),
+            log: BlockState::new(ListState::default(), 0),
+            backups: BackupsState::new(),
         }
     }
 
@@ -135,23 +460,237 @@ impl State {
 pub struct MainView<'a> {
     pub state: &'a mut State,
     pub filtered_items: Vec<ParsedLine<'a>>,
-    pub filter_views: Filters<Vec<String>>,
+    pub filter_views: Filters<Vec<Entry>>,
+    pub prompt: Option<Prompt>,
+    /// what the scripts show after each of the filtered items
+    columns: Vec<Option<String>>,
+    tree: Tree,
+    rows: Vec<Row>,
+    list_rep: &'a ListRep,
+    deps: &'a Deps,
+    clock: &'a dyn Clock,
+    date_config: &'a DateConfig,
 }
 
 impl<'a> MainView<'a> {
     pub fn new(
         state: &'a mut State,
         filtered_items: Vec<ParsedLine<'a>>,
-        filter_views: Filters<Vec<String>>,
+        filter_views: Filters<Vec<Entry>>,
+        list_rep: &'a ListRep,
+        deps: &'a Deps,
+        clock: &'a dyn Clock,
+        date_config: &'a DateConfig,
     ) -> Self {
+        let tree = Tree::new(&filtered_items);
+        let rows = tree.rows(&filtered_items, &state.folded);
+        state.tasks.reset(rows.len());
+        state.contexts.reset(filter_views.contexts.len());
+        state.tags.reset(filter_views.tags.len());
+        let columns = state.scripts.columns(&filtered_items, &mut state.status);
+
         Self {
             state,
             filtered_items,
             filter_views,
+            prompt: None,
+            columns,
+            tree,
+            rows,
+            list_rep,
+            deps,
+            clock,
+            date_config,
+        }
+    }
+
+    fn selected_task(&self) -> Option<&ParsedLine<'a>> {
+        if self.state.active_list != ActiveList::Tasks {
+            return None;
+        }
+        self.state
+            .tasks
+            .pos
+            .selected()
+            .and_then(|i| self.rows.get(i))
+            .map(|r| &self.filtered_items[r.item])
+    }
+
+    fn selected_row(&self) -> Option<&Row> {
+        if self.state.active_list != ActiveList::Tasks {
+            return None;
+        }
+        self.state
+            .tasks
+            .pos
+            .selected()
+            .and_then(|i| self.rows.get(i))
+    }
+
+    /// `draw_details` shows the tasks the selected one depends on and the
+    /// tasks depending on it
+    fn draw_details<B: Backend>(&self, f: &mut Frame<'_, B>, chunk: Rect) {
+        let lines = match self.selected_task() {
+            Some(task) => vec![
+                self.dep_line("Blocked by: ", self.deps.blockers(task.index)),
+                self.dep_line("Blocks: ", self.deps.blocks(task.index)),
+            ],
+            None => Vec::new(),
+        };
+
+        let details = Paragraph::new(lines).block(
+            Block::default()
+                .border_style(Style::default().fg(Color::DarkGray))
+                .borders(Borders::ALL)
+                .title("Details"),
+        );
+        f.render_widget(details, chunk);
+    }
+
+    fn dep_line(&self, label: &'static str, tasks: &[usize]) -> Spans<'_> {
+        let dim = Style::default().fg(Color::DarkGray);
+        let mut spans = vec![Span::styled(label, dim)];
+        if tasks.is_empty() {
+            spans.push(Span::styled("nothing", dim));
+        }
+        for (n, &i) in tasks.iter().enumerate() {
+            if n > 0 {
+                spans.push(Span::styled(", ", dim));
+            }
+            let style = if self.deps.is_complete(i) {
+                dim.add_modifier(Modifier::CROSSED_OUT)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            spans.push(Span::styled(self.deps.body(i), style));
         }
+        Spans::from(spans)
     }
 
-    pub fn draw<B>(&mut self, f: &mut Frame<B>)
+    /// `reparent` nests the selected task under the task before it, or when
+    /// `outdent` is set moves it up alongside its parent
+    fn reparent(&self, outdent: bool) -> Option<Action> {
+        let i = self.selected_row()?.item;
+        let parent = if outdent {
+            let parent = self.tree.parent(i)?;
+            self.tree.parent(parent)
+        } else {
+            Some(self.tree.previous_sibling(i)?)
+        };
+        let index = |i: usize| self.filtered_items[i].index;
+        Some(Action::SetParent(index(i), parent.map(index)))
+    }
+
+    /// `toggle_fold` hides or shows the subtasks of the selected task, or
+    /// the values nested under the selected sidebar node, the rows being
+    /// rebuilt on refresh
+    fn toggle_fold(&mut self) -> Option<Action> {
+        if let Some(entry) = self.selected_entry() {
+            let path = entry.path.clone();
+            if !self.state.collapsed.remove(&path) {
+                self.state.collapsed.insert(path);
+            }
+            return Some(Action::Refresh);
+        }
+        let row = self.selected_row().filter(|row| row.children)?;
+        let id = self.filtered_items[row.item].id()?;
+        if !self.state.folded.remove(id) {
+            self.state.folded.insert(id.to_owned());
+        }
+        Some(Action::Refresh)
+    }
+
+    /// `targets` are the file indices an action applies to, the marked tasks
+    /// if there are any and otherwise the selected one
+    fn targets(&self) -> Vec<usize> {
+        if self.state.marked.is_empty() {
+            self.selected_task().map(|t| t.index).into_iter().collect()
+        } else {
+            self.state.marked.iter().copied().collect()
+        }
+    }
+
+    /// `selected_entry` is the context or tag selected in the sidebar
+    fn selected_entry(&self) -> Option<&Entry> {
+        let pos = match self.state.active_list {
+            ActiveList::Tasks => return None,
+            ActiveList::Contexts => self.state.contexts.pos.selected()?,
+            ActiveList::Tags => self.state.tags.pos.selected()?,
+        };
+        self.filter_views.get(self.state.active_list.filter()?).get(pos)
+    }
+
+    /// `attribute_key` maps the keys acting on the context or project
+    /// selected in the sidebar, which rewrite it across both files
+    fn attribute_key(&self, key: Key) -> Option<Command> {
+        let entry = self.selected_entry()?;
+        let kind = self.state.active_list.filter()?;
+        let name = self.list_rep.filters.get(kind).get(entry.index)?;
+        // a parent node in the sidebar tree isn't a value to rewrite
+        if name.len() != entry.len {
+            return None;
+        }
+        let lines = self
+            .list_rep
+            .tasks
+            .iter()
+            .chain(&self.list_rep.done)
+            .filter(|line| line.split(' ').any(|word| word == name))
+            .count();
+
+        let kind = match key {
+            Key::Char('e') => PromptKind::Rename(name.clone(), lines),
+            Key::Char('J') => PromptKind::Merge(name.clone(), lines),
+            Key::Char('D') => PromptKind::Strip(name.clone(), lines),
+            _ => return None,
+        };
+        Some(Command::Ask(Prompt::open(kind, None)))
+    }
+
+    fn draw_attributes<B: Backend>(
+        &mut self,
+        f: &mut Frame<'_, B>,
+        selected_style: Style,
+        list_t: ActiveList,
+        chunk: Rect,
+    ) {
+        let Some(kind) = list_t.filter() else {
+            return;
+        };
+        let list_items: Vec<ListItem> = self
+            .filter_views
+            .get(kind)
+            .iter()
+            .map(|entry| ListItem::new(Span::raw(&entry.label[..])))
+            .collect();
+
+        // the sort and faceting are only noted when they aren't the default
+        let mut title = list_t.to_string().to_owned();
+        if self.state.sidebar.order != Order::Name {
+            title = format!("{} · {}", title, self.state.sidebar.order.name());
+        }
+        if self.state.sidebar.faceted {
+            title.push_str(" · faceted");
+        }
+
+        let list = List::new(list_items)
+            .block(
+                Block::default()
+                    .border_style(self.state.get_style(list_t))
+                    .borders(Borders::ALL)
+                    .title(title),
+            )
+            .highlight_symbol("*")
+            .highlight_style(selected_style);
+
+        let block_state = self.state.get_state_mut(list_t);
+
+        f.render_stateful_widget(list, chunk, &mut block_state.pos);
+    }
+}
+
+impl<'a> View for MainView<'a> {
+    fn draw<B>(&mut self, f: &mut Frame<B>)
     where
         B: Backend,
     {
@@ -159,11 +698,16 @@ impl<'a> MainView<'a> {
             .fg(Color::Green)
             .add_modifier(Modifier::BOLD);
 
+        let outer_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Min(0), Length(1)].as_ref())
+            .split(f.size());
+
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .margin(1)
             .constraints([Percentage(80), Percentage(20)].as_ref())
-            .split(f.size());
+            .split(outer_chunks[0]);
 
         let attr_chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -173,23 +717,51 @@ impl<'a> MainView<'a> {
         self.draw_attributes(f, selected_style, ActiveList::Contexts, attr_chunks[0]);
         self.draw_attributes(f, selected_style, ActiveList::Tags, attr_chunks[1]);
 
+        let items = &self.filtered_items;
         let list_items: Vec<ListItem> = self
-            .filtered_items
+            .rows
             .iter()
-            .map(|state_item| {
-                let sub_text = state_item.start_date.unwrap_or("");
+            .map(|row| {
+                let state_item = &items[row.item];
+                let mark = if self.state.marked.contains(&state_item.index) {
+                    Span::styled("+ ", Style::default().fg(Color::Cyan))
+                } else {
+                    Span::raw("")
+                };
+                let sub_text = describe_dates(
+                    state_item,
+                    self.state.today,
+                    self.state.relative_dates,
+                    &self.date_config.format,
+                );
+                let blocked = self.deps.is_blocked(state_item.index);
                 let lines = vec![
-                    Spans::from(Span::styled(
-                        &state_item.body[..],
-                        Style::default()
-                            .fg(Color::White)
-                            .add_modifier(if state_item.complete {
-                                Modifier::CROSSED_OUT
-                            } else {
-                                Modifier::BOLD
-                            }),
-                    )),
-                    Spans::from(Span::styled(sub_text, Style::default().fg(Color::DarkGray))),
+                    Spans::from(vec![
+                        tree_prefix(row),
+                        mark,
+                        if blocked {
+                            Span::styled("⊘ ", Style::default().fg(Color::Red))
+                        } else {
+                            Span::raw("")
+                        },
+                        Span::styled(
+                            &state_item.body[..],
+                            Style::default()
+                                .fg(if blocked {
+                                    Color::DarkGray
+                                } else {
+                                    Color::White
+                                })
+                                .add_modifier(if state_item.complete {
+                                    Modifier::CROSSED_OUT
+                                } else {
+                                    Modifier::BOLD
+                                }),
+                        ),
+                        progress(row),
+                        column(self.columns[row.item].as_deref()),
+                    ]),
+                    sub_text,
                 ];
 
                 ListItem::new(lines)
@@ -201,82 +773,266 @@ impl<'a> MainView<'a> {
                 Block::default()
                     .border_style(self.state.get_style(ActiveList::Tasks))
                     .borders(Borders::ALL)
-                    .title("Tasks"),
+                    .title(if self.state.actionable_only {
+                        "Tasks (actionable)"
+                    } else {
+                        "Tasks"
+                    }),
             )
             .highlight_style(selected_style)
             .highlight_symbol("*");
 
-        f.render_stateful_widget(list, chunks[0], &mut self.state.tasks.pos);
-    }
-
-    fn draw_attributes<B: Backend>(
-        &mut self,
-        f: &mut Frame<'_, B>,
-        selected_style: Style,
-        list_t: ActiveList,
-        chunk: Rect,
-    ) {
-        let list_items: Vec<ListItem> = self
-            .filter_views
-            .get(list_t)
-            .iter()
-            .map(|i| ListItem::new(Span::raw(i)))
-            .collect();
-
-        let list = List::new(list_items)
-            .block(
-                Block::default()
-                    .border_style(self.state.get_style(list_t))
-                    .borders(Borders::ALL)
-                    .title(list_t.to_string()),
-            )
-            .highlight_symbol("*")
-            .highlight_style(selected_style);
+        let task_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Min(0), Length(4)].as_ref())
+            .split(chunks[0]);
 
-        let block_state = self.state.get_state_mut(list_t);
+        f.render_stateful_widget(list, task_chunks[0], &mut self.state.tasks.pos);
+        self.draw_details(f, task_chunks[1]);
 
-        f.render_stateful_widget(list, chunk, &mut block_state.pos);
+        let status_chunk = draw_timer(
+            f,
+            outer_chunks[1],
+            self.state.timer.as_ref(),
+            self.clock.now(),
+        );
+        draw_status_bar(
+            f,
+            status_chunk,
+            self.prompt.as_ref(),
+            self.state.status.as_ref(),
+        );
     }
 }
 
 impl<'a> EventHandler<Key> for MainView<'a> {
     fn handle(&mut self, event: Event<Key>) -> Option<Action> {
+        if let Event::Input(_) = event {
+            self.state.status = None;
+        }
+
         match event {
+            Event::Input(key) if self.prompt.is_some() => return Prompt::handle(&mut self.prompt, key),
             Event::Input(key) => match key {
                 Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => return Some(Action::Exit),
                 Key::Char('j') => self.state.next(),
                 Key::Char('k') => self.state.previous(),
                 Key::Char('l') => self.state.move_right(),
                 Key::Char('h') => self.state.move_left(),
-                Key::Char(' ') => {
-                    let index = match self.state.active_list {
-                        ActiveList::Tasks => self
-                            .state
-                            .tasks
-                            .pos
-                            .selected()
-                            .map(|i| self.filtered_items[i].index),
-                        ActiveList::Contexts => self.state.contexts.pos.selected(),
-                        ActiveList::Tags => self.state.tags.pos.selected(),
-                    };
-
-                    if let Some(i) = index {
-                        return Some(Action::Select(i));
+                Key::Char(' ') if self.state.active_list != ActiveList::Tasks => {
+                    if let Some(entry) = self.selected_entry() {
+                        return Some(Action::Select(entry.index, entry.len));
                     }
                 }
+                Key::Char('N') => {
+                    self.state.sidebar.faceted = !self.state.sidebar.faceted;
+                    return Some(Action::Refresh);
+                }
+                Key::Char('o') => {
+                    self.state.sidebar.order = self.state.sidebar.order.next();
+                    return Some(Action::Refresh);
+                }
                 Key::Char('w') => return Some(Action::Write),
-                Key::Char('D') => {
-                    if ActiveList::Tasks == self.state.active_list {
-                        if let Some(i) = self.state.tasks.pos.selected() {
-                            return Some(Action::Delete(self.filtered_items[i].index));
+                Key::Char('W') => return Some(Action::ForceWrite),
+                Key::Char('u') => return Some(Action::Undo),
+                Key::Char('r') => return Some(Action::Reload),
+                Key::Char('c') => return Some(Action::SwitchView(ViewKind::Calendar)),
+                Key::Char('g') => return Some(Action::SwitchView(ViewKind::Agenda)),
+                Key::Char('b') => return Some(Action::SwitchView(ViewKind::Board)),
+                Key::Char('S') => return Some(Action::SwitchView(ViewKind::Stats)),
+                Key::Char('P') => return Some(Action::SwitchView(ViewKind::Projects)),
+                Key::Char('L') => return Some(Action::SwitchView(ViewKind::Log)),
+                Key::Char('B') => return Some(Action::SwitchView(ViewKind::Backups)),
+                Key::Char('p') => {
+                    if let Some(i) = self.selected_task().map(|t| t.index) {
+                        return Some(Action::StartPomodoro(i));
+                    }
+                    return Some(Action::SwitchView(ViewKind::Pomodoro));
+                }
+                Key::Char('R') => self.state.relative_dates = !self.state.relative_dates,
+                Key::Char('m') => {
+                    if let Some(i) = self.selected_task().map(|t| t.index) {
+                        if !self.state.marked.remove(&i) {
+                            self.state.marked.insert(i);
                         }
+                        self.state.next();
                     }
                 }
-                Key::Char('r') => return Some(Action::Reload),
-                _ => {}
+                Key::Char('M') => self.state.marked.clear(),
+                Key::Char('z') => return self.toggle_fold(),
+                Key::Char('n') => {
+                    self.state.actionable_only = !self.state.actionable_only;
+                    return Some(Action::Refresh);
+                }
+                Key::Char('>') => return self.reparent(false),
+                Key::Char('<') => return self.reparent(true),
+                Key::Char('A') => {
+                    if let Some(i) = self.selected_task().map(|t| t.index) {
+                        self.prompt = Some(Prompt::new(PromptKind::AddChild(i), String::new()));
+                    }
+                }
+                Key::Char('X') => {
+                    self.prompt = Some(Prompt::new(PromptKind::Addon, String::new()));
+                }
+                Key::Char(':') => {
+                    let selected = self.selected_task().map(|t| t.index);
+                    self.prompt = Some(Prompt::new(PromptKind::Script(selected), String::new()));
+                }
+                Key::Char('F') => {
+                    self.state.show_deferred = !self.state.show_deferred;
+                    return Some(Action::Refresh);
+                }
+                key => {
+                    if let Some(command) = self.attribute_key(key) {
+                        return command.apply(&mut self.prompt);
+                    }
+                    let targets = self.targets();
+                    return task_key(key, self.selected_task(), targets)
+                        .and_then(|c| c.apply(&mut self.prompt));
+                }
             },
-            Event::_Tick => {}
+            Event::Tick => {
+                // the view is redrawn after every event, so moving `today`
+                // forward is enough to refresh relative dates on rollover
+                let today = self.clock.today();
+                if today != self.state.today {
+                    self.state.today = today;
+                }
+            }
         };
         None
     }
 }
+
+/// `tree_prefix` indents a task by its depth, marking tasks with subtasks by
+/// whether they're folded
+fn tree_prefix(row: &Row) -> Span<'static> {
+    let marker = match (row.children, row.folded) {
+        (true, true) => "▸ ",
+        (true, false) => "▾ ",
+        (false, _) if row.depth > 0 => "· ",
+        (false, _) => "",
+    };
+    Span::styled(
+        format!("{}{}", "  ".repeat(row.depth), marker),
+        Style::default().fg(Color::DarkGray),
+    )
+}
+
+/// `progress` shows how many of a task's subtasks are done
+fn progress(row: &Row) -> Span<'static> {
+    match row.progress {
+        Some((done, total)) => Span::styled(
+            format!(" [{}/{} {}%]", done, total, done * 100 / total),
+            Style::default().fg(if done == total {
+                Color::Green
+            } else {
+                Color::DarkGray
+            }),
+        ),
+        None => Span::raw(""),
+    }
+}
+
+/// `column` shows what the scripts computed for a task
+fn column(text: Option<&str>) -> Span<'static> {
+    match text {
+        Some(text) => Span::styled(format!("  {}", text), Style::default().fg(Color::Cyan)),
+        None => Span::raw(""),
+    }
+}
+
+/// `describe_dates` renders the subtitle line of a task from its due,
+/// threshold, creation and completion dates, either relative to `today` or
+/// in the absolute `format`
+fn describe_dates(
+    item: &ParsedLine,
+    today: NaiveDate,
+    relative: bool,
+    format: &str,
+) -> Spans<'static> {
+    let dates = [
+        ("due", item.due_date),
+        ("t", item.threshold_date),
+        ("added", item.start_date),
+        ("done", item.completion_date),
+    ];
+
+    let mut spans = Vec::new();
+    for (kind, d) in &dates {
+        let Some(d) = d.and_then(date::from_iso) else {
+            continue;
+        };
+
+        let span = if relative {
+            describe_relative(kind, d, today, item.complete)
+        } else {
+            let label = if *kind == "t" { "starts" } else { kind };
+            Some(Span::styled(
+                format!("{} {}", label, d.format(format)),
+                Style::default().fg(Color::DarkGray),
+            ))
+        };
+
+        if let Some(span) = span {
+            if !spans.is_empty() {
+                spans.push(Span::raw("  "));
+            }
+            spans.push(span);
+        }
+    }
+
+    Spans::from(spans)
+}
+
+fn describe_relative(
+    kind: &str,
+    d: NaiveDate,
+    today: NaiveDate,
+    complete: bool,
+) -> Option<Span<'static>> {
+    let dim = Style::default().fg(Color::DarkGray);
+    let days = (d - today).num_days();
+
+    let (text, style) = match kind {
+        "due" if complete => return None,
+        "due" if days < 0 => (
+            format!("overdue {}", date::span(days)),
+            Style::default().fg(Color::Red),
+        ),
+        "due" if days == 0 => ("due today".to_owned(), Style::default().fg(Color::Yellow)),
+        "due" if days == 1 => ("due tomorrow".to_owned(), dim),
+        "due" if days < 14 => (format!("due in {} days", days), dim),
+        "due" => (format!("due in {}", date::span(days)), dim),
+        "t" if days > 0 => (format!("starts in {}", date::span(days)), dim),
+        "t" => return None,
+        _ if days == 0 => (format!("{} today", kind), dim),
+        _ => (format!("{} {} ago", kind, date::span(days)), dim),
+    };
+
+    Some(Span::styled(text, style))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_wraps_after_the_last_row() {
+        let mut state = BlockState::new(ListState::default(), 2);
+        state.next();
+        assert_eq!(state.pos.selected(), Some(0));
+        state.next();
+        assert_eq!(state.pos.selected(), Some(1));
+        state.next();
+        assert_eq!(state.pos.selected(), Some(0));
+    }
+
+    #[test]
+    fn nothing_to_move_to_in_an_empty_list() {
+        let mut state = BlockState::new(ListState::default(), 0);
+        state.previous();
+        state.next();
+        assert_eq!(state.pos.selected(), None);
+    }
+}
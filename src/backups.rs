@@ -0,0 +1,221 @@
+use crate::{
+    app::{BlockState, View, ViewKind},
+    event::{Event, Handler as EventHandler},
+    runner::Action,
+    todo::{backup::Backup, ListHandle},
+};
+
+use std::collections::HashSet;
+use termion::event::Key;
+use tui::{
+    backend::Backend,
+    layout::{
+        Constraint::{Length, Min},
+        Direction, Layout,
+    },
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+/// `Backed` is which of the files the backups shown are of
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backed {
+    Todo,
+    Done,
+}
+
+impl Backed {
+    fn name(self) -> &'static str {
+        match self {
+            Backed::Todo => "todo",
+            Backed::Done => "done",
+        }
+    }
+}
+
+/// `BackupsState` is the part of the backups view that outlives a single view
+pub struct BackupsState {
+    pub file: Backed,
+    list: BlockState,
+}
+
+impl BackupsState {
+    pub fn new() -> Self {
+        Self {
+            file: Backed::Todo,
+            list: BlockState::new(ListState::default(), 0),
+        }
+    }
+}
+
+/// `Saved` is a backup and the lines it holds
+struct Saved {
+    backup: Backup,
+    lines: Result<Vec<String>, String>,
+}
+
+/// `BackupsView` lists the backups of the todo or done file, newest first,
+/// showing the selected one with the lines the file no longer has picked out
+pub struct BackupsView<'a> {
+    state: &'a mut BackupsState,
+    status: &'a mut Option<String>,
+    saved: Vec<Saved>,
+    current: HashSet<&'a str>,
+}
+
+impl<'a> BackupsView<'a> {
+    /// `new` shows `backups`, which are of the file holding `lines`
+    pub fn new(
+        state: &'a mut BackupsState,
+        status: &'a mut Option<String>,
+        backups: Vec<Backup>,
+        lines: &'a [String],
+    ) -> Self {
+        let saved: Vec<Saved> = backups
+            .into_iter()
+            .map(|backup| {
+                let lines = ListHandle::new(&backup.path)
+                    .get_lines()
+                    .map_err(|e| e.to_string());
+                Saved { backup, lines }
+            })
+            .collect();
+        state.list.reset(saved.len());
+        if state.list.pos.selected().is_none() && !saved.is_empty() {
+            state.list.pos.select(Some(0));
+        }
+
+        Self {
+            state,
+            status,
+            saved,
+            current: lines.iter().map(|l| &l[..]).collect(),
+        }
+    }
+
+    fn selected(&self) -> Option<&Saved> {
+        self.state.list.pos.selected().and_then(|i| self.saved.get(i))
+    }
+
+    /// `preview` is the selected backup, with the lines which aren't in the
+    /// file any more in green
+    fn preview(&self) -> (String, Vec<ListItem<'_>>) {
+        let Some(saved) = self.selected() else {
+            return ("Backup".to_owned(), Vec::new());
+        };
+        let lines = match &saved.lines {
+            Ok(lines) => lines,
+            Err(e) => {
+                let error = Span::styled(e.clone(), Style::default().fg(Color::Red));
+                return ("Backup".to_owned(), vec![ListItem::new(error)]);
+            }
+        };
+
+        let kept: HashSet<&str> = lines.iter().map(|l| &l[..]).collect();
+        let missing = lines.iter().filter(|l| !self.current.contains(&l[..])).count();
+        let added = self.current.iter().filter(|l| !kept.contains(*l)).count();
+        let title = format!(
+            "Backup: {} lines, {} not in the {} file, which has {} more",
+            lines.len(),
+            missing,
+            self.state.file.name(),
+            added
+        );
+
+        let items = lines
+            .iter()
+            .map(|line| {
+                let style = if self.current.contains(&line[..]) {
+                    Style::default()
+                } else {
+                    Style::default().fg(Color::Green)
+                };
+                ListItem::new(Span::styled(line.clone(), style))
+            })
+            .collect();
+        (title, items)
+    }
+}
+
+impl<'a> View for BackupsView<'a> {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Min(0), Length(1)].as_ref())
+            .split(f.size());
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Length(28), Min(0)].as_ref())
+            .split(rows[0]);
+
+        let (title, preview) = self.preview();
+        f.render_widget(
+            List::new(preview).block(Block::default().borders(Borders::ALL).title(title)),
+            panes[1],
+        );
+
+        let items: Vec<ListItem> = self
+            .saved
+            .iter()
+            .map(|saved| {
+                ListItem::new(Span::raw(
+                    saved.backup.taken.format("%Y-%m-%d %H:%M:%S").to_string(),
+                ))
+            })
+            .collect();
+        let title = format!("Backups of {}", self.state.file.name());
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, panes[0], &mut self.state.list.pos);
+
+        let status = match (&self.status, self.saved.is_empty()) {
+            (Some(s), _) => Span::styled(&s[..], Style::default().fg(Color::Yellow)),
+            (None, true) => Span::styled(
+                "no backups taken yet",
+                Style::default().fg(Color::DarkGray),
+            ),
+            (None, false) => Span::styled(
+                "enter to restore the selected backup, which can be undone, tab for the other file",
+                Style::default().fg(Color::DarkGray),
+            ),
+        };
+        f.render_widget(Paragraph::new(Spans::from(status)), rows[1]);
+    }
+}
+
+impl<'a> EventHandler<Key> for BackupsView<'a> {
+    fn handle(&mut self, event: Event<Key>) -> Option<Action> {
+        let Event::Input(key) = event else {
+            return None;
+        };
+
+        *self.status = None;
+        match key {
+            Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => return Some(Action::Exit),
+            Key::Char('j') | Key::Down => self.state.list.next(),
+            Key::Char('k') | Key::Up => self.state.list.previous(),
+            Key::Char('\t') => {
+                self.state.file = match self.state.file {
+                    Backed::Todo => Backed::Done,
+                    Backed::Done => Backed::Todo,
+                };
+                self.state.list.pos.select(None);
+                return Some(Action::Refresh);
+            }
+            Key::Char('\n') => {
+                if let Some(saved) = self.selected() {
+                    let backup = saved.backup.clone();
+                    return Some(Action::RestoreBackup(backup, self.state.file));
+                }
+            }
+            Key::Char('r') => return Some(Action::Reload),
+            Key::Char('B') | Key::Esc => return Some(Action::SwitchView(ViewKind::Main)),
+            _ => {}
+        }
+        None
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{
+    app::{draw_status_bar, draw_timer, task_key, BlockState, Prompt, View, ViewKind},
+    config::{BoardConfig, PRIORITY_KEY},
+    event::{Event, Handler as EventHandler},
+    runner::Action,
+    timer::Timer,
+    todo::{date::Clock, edit, ParsedLine},
+};
+
+use std::convert::TryFrom;
+use termion::event::Key;
+use tui::{
+    backend::Backend,
+    layout::{
+        Constraint::{Length, Min, Ratio},
+        Direction, Layout,
+    },
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Frame,
+};
+
+/// `BoardState` is the part of the board that outlives a single view
+pub struct BoardState {
+    column: usize,
+    cards: Vec<BlockState>,
+}
+
+impl BoardState {
+    pub fn new() -> Self {
+        Self {
+            column: 0,
+            cards: Vec::new(),
+        }
+    }
+}
+
+pub struct BoardView<'a> {
+    state: &'a mut BoardState,
+    status: &'a mut Option<String>,
+    config: &'a BoardConfig,
+    columns: Vec<String>,
+    /// indices into `items` of the cards in each column
+    cards: Vec<Vec<usize>>,
+    items: Vec<ParsedLine<'a>>,
+    prompt: Option<Prompt>,
+    timer: Option<&'a Timer>,
+    clock: &'a dyn Clock,
+}
+
+impl<'a> BoardView<'a> {
+    pub fn new(
+        state: &'a mut BoardState,
+        status: &'a mut Option<String>,
+        config: &'a BoardConfig,
+        items: Vec<ParsedLine<'a>>,
+        timer: Option<&'a Timer>,
+        clock: &'a dyn Clock,
+    ) -> Self {
+        let columns = config.columns();
+        let mut cards = vec![Vec::new(); columns.len()];
+        for (i, item) in items.iter().enumerate() {
+            if item.complete {
+                continue;
+            }
+            // cards without a recognised value start in the first column
+            let value = column_value(config, item);
+            let column = columns.iter().position(|c| *c == value).unwrap_or(0);
+            cards[column].push(i);
+        }
+
+        state
+            .cards
+            .resize_with(columns.len(), || BlockState::new(ListState::default(), 0));
+        for (block, cards) in state.cards.iter_mut().zip(&cards) {
+            block.reset(cards.len());
+        }
+        state.column = state.column.min(columns.len().saturating_sub(1));
+
+        Self {
+            state,
+            status,
+            config,
+            columns,
+            cards,
+            items,
+            prompt: None,
+            timer,
+            clock,
+        }
+    }
+
+    fn selected_task(&self) -> Option<&ParsedLine<'a>> {
+        let column = self.state.column;
+        self.state.cards[column]
+            .pos
+            .selected()
+            .and_then(|i| self.cards[column].get(i))
+            .map(|&i| &self.items[i])
+    }
+
+    fn is_full(&self, column: usize) -> bool {
+        self.config
+            .wip
+            .get(&self.columns[column])
+            .is_some_and(|&limit| self.cards[column].len() >= limit)
+    }
+
+    /// `move_card` shifts the selected card to the neighbouring column by
+    /// rewriting its line. Moving right out of the last column completes it.
+    fn move_card(&mut self, right: bool) -> Option<Action> {
+        let task = self.selected_task()?;
+        let (index, raw) = (task.index, task.raw);
+        let column = self.state.column;
+        let last = self.columns.len() - 1;
+
+        let target = match (right, column) {
+            (true, c) if c == last => return Some(Action::Complete(index)),
+            (true, c) => c + 1,
+            (false, 0) => return None,
+            (false, c) => c - 1,
+        };
+
+        if self.is_full(target) {
+            *self.status = Some(format!("WIP limit reached for {}", self.columns[target]));
+            return None;
+        }
+
+        let value = &self.columns[target];
+        let line = if self.config.key == PRIORITY_KEY {
+            edit::set_priority(raw, value.chars().next().filter(|&c| c != '-'))
+        } else {
+            edit::set_extension(raw, &self.config.key, value)
+        };
+
+        self.state.column = target;
+        Some(Action::Edit(index, line))
+    }
+}
+
+/// `column_value` is the value a card is sorted into a column by
+fn column_value(config: &BoardConfig, item: &ParsedLine) -> String {
+    if config.key == PRIORITY_KEY {
+        return item.priority.map_or("-".to_owned(), |p| p.to_string());
+    }
+    edit::get_extension(item.raw, &config.key)
+        .unwrap_or_default()
+        .to_owned()
+}
+
+impl<'a> View for BoardView<'a> {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let outer_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Min(0), Length(1)].as_ref())
+            .split(f.size());
+
+        let count = u32::try_from(self.columns.len()).unwrap_or(1);
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints((0..count).map(|_| Ratio(1, count)).collect::<Vec<_>>())
+            .split(outer_chunks[0]);
+
+        for (column, chunk) in chunks.into_iter().enumerate() {
+            let name = &self.columns[column];
+            let cards = &self.cards[column];
+            let limit = self.config.wip.get(name);
+
+            let title = match limit {
+                Some(limit) => format!("{} {}/{}", name, cards.len(), limit),
+                None => format!("{} {}", name, cards.len()),
+            };
+            let over = limit.is_some_and(|&l| cards.len() > l);
+            let title_style = if over {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+
+            let items = &self.items;
+            let list_items: Vec<ListItem> = cards
+                .iter()
+                .map(|&i| ListItem::new(Spans::from(Span::raw(&items[i].body[..]))))
+                .collect();
+
+            let border_style = if column == self.state.column {
+                Style::default().fg(Color::White)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+
+            let list = List::new(list_items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(border_style)
+                        .title(Span::styled(title, title_style)),
+                )
+                .highlight_style(
+                    Style::default()
+                        .fg(Color::Green)
+                        .add_modifier(Modifier::BOLD),
+                )
+                .highlight_symbol("*");
+
+            f.render_stateful_widget(list, chunk, &mut self.state.cards[column].pos);
+        }
+
+        let status_chunk = draw_timer(f, outer_chunks[1], self.timer, self.clock.now());
+        draw_status_bar(f, status_chunk, self.prompt.as_ref(), self.status.as_ref());
+    }
+}
+
+impl<'a> EventHandler<Key> for BoardView<'a> {
+    fn handle(&mut self, event: Event<Key>) -> Option<Action> {
+        let key = match event {
+            Event::Input(key) => key,
+            Event::Tick => return None,
+        };
+        if self.prompt.is_some() {
+            return Prompt::handle(&mut self.prompt, key);
+        }
+
+        *self.status = None;
+        let last = self.columns.len() - 1;
+        match key {
+            Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => return Some(Action::Exit),
+            Key::Char('j') | Key::Down => self.state.cards[self.state.column].next(),
+            Key::Char('k') | Key::Up => self.state.cards[self.state.column].previous(),
+            Key::Char('h') | Key::Left => self.state.column = self.state.column.saturating_sub(1),
+            Key::Char('l') | Key::Right => self.state.column = (self.state.column + 1).min(last),
+            Key::Char('H' | '<') => return self.move_card(false),
+            Key::Char('L' | '>') => return self.move_card(true),
+            Key::Char('w') => return Some(Action::Write),
+            Key::Char('W') => return Some(Action::ForceWrite),
+            Key::Char('r') => return Some(Action::Reload),
+            Key::Char('b') | Key::Esc => return Some(Action::SwitchView(ViewKind::Main)),
+            key => {
+                let task = self.selected_task();
+                let targets = task.map(|t| t.index).into_iter().collect();
+                return task_key(key, task, targets).and_then(|c| c.apply(&mut self.prompt));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,394 @@
+use crate::{
+    app::{draw_timer, BlockState, View, ViewKind},
+    event::{Event, Handler as EventHandler},
+    runner::Action,
+    timer::Timer,
+    todo::{
+        date::{self, Clock},
+        ParsedLine,
+    },
+};
+
+use chrono::{Datelike, Duration, NaiveDate};
+use termion::event::Key;
+use tui::{
+    backend::Backend,
+    layout::{
+        Constraint::{Length, Min, Percentage, Ratio},
+        Direction, Layout, Rect,
+    },
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Focus {
+    Grid,
+    Day,
+}
+
+/// `CalendarState` is the part of the calendar that outlives a single view
+pub struct CalendarState {
+    pub cursor: NaiveDate,
+    day: BlockState,
+    focus: Focus,
+    /// the task being rescheduled, with whether its due date (rather than its
+    /// threshold) is the one being moved
+    moving: Option<(usize, bool)>,
+}
+
+impl CalendarState {
+    pub fn new(today: NaiveDate) -> Self {
+        Self {
+            cursor: today,
+            day: BlockState::new(ListState::default(), 0),
+            focus: Focus::Grid,
+            moving: None,
+        }
+    }
+}
+
+/// `Entry` is a task placed on a day of the calendar, either because it's due
+/// then or because its threshold starts then
+struct Entry<'a> {
+    item: &'a ParsedLine<'a>,
+    due: bool,
+}
+
+pub struct CalendarView<'a> {
+    state: &'a mut CalendarState,
+    status: &'a mut Option<String>,
+    today: &'a mut NaiveDate,
+    items: Vec<ParsedLine<'a>>,
+    timer: Option<&'a Timer>,
+    clock: &'a dyn Clock,
+}
+
+impl<'a> CalendarView<'a> {
+    pub fn new(
+        state: &'a mut CalendarState,
+        status: &'a mut Option<String>,
+        today: &'a mut NaiveDate,
+        items: Vec<ParsedLine<'a>>,
+        timer: Option<&'a Timer>,
+        clock: &'a dyn Clock,
+    ) -> Self {
+        Self {
+            state,
+            status,
+            today,
+            items,
+            timer,
+            clock,
+        }
+    }
+
+    fn entries(&self, day: NaiveDate) -> Vec<Entry<'_>> {
+        entries(&self.items, day)
+    }
+
+    fn is_overdue(&self, day: NaiveDate) -> bool {
+        day < *self.today && self.entries(day).iter().any(|e| e.due && !e.item.complete)
+    }
+
+    fn move_cursor(&mut self, days: i64) {
+        self.state.cursor += Duration::days(days);
+        self.state.day.pos = ListState::default();
+    }
+
+    fn move_month(&mut self, months: i64) {
+        if let Some(d) = date::add_months(self.state.cursor, months) {
+            self.state.cursor = d;
+            self.state.day.pos = ListState::default();
+        }
+    }
+
+    fn draw_grid<B: Backend>(&self, f: &mut Frame<'_, B>, chunk: Rect) {
+        let cursor = self.state.cursor;
+        let first = date::first_of_month(cursor);
+        let next_month = date::add_months(first, 1).unwrap_or(first);
+        let lead = first.weekday().num_days_from_monday();
+        let month_days = next_month.pred().day();
+        let weeks = (lead + month_days).div_ceil(7);
+        let start = first - Duration::days(i64::from(lead));
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(border_style(self.state.focus, Focus::Grid))
+            .title(Span::styled(
+                cursor.format(" %B %Y ").to_string(),
+                Style::default().add_modifier(Modifier::BOLD),
+            ));
+        let inner = block.inner(chunk);
+        f.render_widget(block, chunk);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                std::iter::once(Length(1))
+                    .chain((0..weeks).map(|_| Ratio(1, weeks)))
+                    .collect::<Vec<_>>(),
+            )
+            .split(inner);
+
+        let columns = |row: Rect| {
+            Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Ratio(1, 7); 7].as_ref())
+                .split(row)
+        };
+
+        let mut day = start;
+        for cell in columns(rows[0]) {
+            let name = day.format("%a").to_string();
+            f.render_widget(
+                Paragraph::new(Span::styled(name, Style::default().fg(Color::DarkGray))),
+                cell,
+            );
+            day = day.succ();
+        }
+
+        let mut day = start;
+        for row in rows.into_iter().skip(1) {
+            for cell in columns(row) {
+                self.draw_day(f, day, cell);
+                day = day.succ();
+            }
+        }
+    }
+
+    fn draw_day<B: Backend>(&self, f: &mut Frame<'_, B>, day: NaiveDate, cell: Rect) {
+        let in_month = day.month() == self.state.cursor.month();
+        let mut border = Style::default().fg(if in_month {
+            Color::Gray
+        } else {
+            Color::DarkGray
+        });
+        if self.is_overdue(day) {
+            border = border.fg(Color::Red);
+        }
+        if day == self.state.cursor {
+            border = border.fg(Color::Green).add_modifier(Modifier::BOLD);
+        }
+
+        let mut title = Style::default();
+        if day == *self.today {
+            title = title.fg(Color::Black).bg(Color::Yellow);
+        }
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(border)
+            .title(Span::styled(format!("{}", day.day()), title));
+        let inner = block.inner(cell);
+        f.render_widget(block, cell);
+
+        let entries = self.entries(day);
+        let room = inner.height as usize;
+        let lines: Vec<Spans> = if entries.len() <= room {
+            entries.iter().map(entry_line).collect()
+        } else {
+            let due = entries.iter().filter(|e| e.due).count();
+            let starts = entries.len() - due;
+            vec![Spans::from(vec![
+                Span::raw(format!("{} due ", due)),
+                Span::styled(format!("{} start", starts), start_style()),
+            ])]
+        };
+
+        f.render_widget(Paragraph::new(lines), inner);
+    }
+
+    fn draw_day_list<B: Backend>(&mut self, f: &mut Frame<'_, B>, chunk: Rect) {
+        let entries = entries(&self.items, self.state.cursor);
+        let list_items: Vec<ListItem> = entries
+            .iter()
+            .map(|e| ListItem::new(entry_line(e)))
+            .collect();
+        let len = list_items.len();
+
+        let list = List::new(list_items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(border_style(self.state.focus, Focus::Day))
+                    .title(self.state.cursor.format("%a %-d %b").to_string()),
+            )
+            .highlight_style(
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol("*");
+
+        self.state.day.reset(len);
+        f.render_stateful_widget(list, chunk, &mut self.state.day.pos);
+    }
+
+    fn draw_status_bar<B: Backend>(&self, f: &mut Frame<'_, B>, chunk: Rect) {
+        let chunk = draw_timer(f, chunk, self.timer, self.clock.now());
+        let line = if let Some((i, _)) = self.state.moving {
+            let body = self
+                .items
+                .iter()
+                .find(|item| item.index == i)
+                .map_or("", |item| &item.body[..]);
+            Spans::from(vec![
+                Span::styled("Moving: ", Style::default().fg(Color::Green)),
+                Span::raw(body),
+                Span::styled(
+                    "  (enter to drop, esc to cancel)",
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ])
+        } else if let Some(status) = self.status.as_ref() {
+            Spans::from(Span::styled(&status[..], Style::default().fg(Color::Yellow)))
+        } else {
+            Spans::default()
+        };
+
+        f.render_widget(Paragraph::new(line), chunk);
+    }
+
+    fn handle_grid(&mut self, key: Key) -> Option<Action> {
+        match key {
+            Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => return Some(Action::Exit),
+            Key::Esc if self.state.moving.is_some() => self.state.moving = None,
+            Key::Char('c') | Key::Esc => return Some(Action::SwitchView(ViewKind::Main)),
+            Key::Char('h') | Key::Left => self.move_cursor(-1),
+            Key::Char('l') | Key::Right => self.move_cursor(1),
+            Key::Char('k') | Key::Up => self.move_cursor(-7),
+            Key::Char('j') | Key::Down => self.move_cursor(7),
+            Key::Char('p') => self.move_month(-1),
+            Key::Char('n') => self.move_month(1),
+            Key::Char('t') => {
+                self.state.cursor = *self.today;
+                self.state.day.pos = ListState::default();
+            }
+            Key::Char('\n') => {
+                let target = self.state.cursor.format(date::FORMAT).to_string();
+                match self.state.moving.take() {
+                    Some((i, true)) => return Some(Action::SetDue(i, target)),
+                    Some((i, false)) => return Some(Action::Snooze(i, target)),
+                    None if !self.entries(self.state.cursor).is_empty() => {
+                        self.state.focus = Focus::Day;
+                        self.state.day.pos.select(Some(0));
+                    }
+                    None => {}
+                }
+            }
+            Key::Char('w') => return Some(Action::Write),
+            Key::Char('W') => return Some(Action::ForceWrite),
+            _ => {}
+        }
+        None
+    }
+
+    fn handle_day(&mut self, key: Key) -> Option<Action> {
+        match key {
+            Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => return Some(Action::Exit),
+            Key::Esc | Key::Char('h') => self.state.focus = Focus::Grid,
+            Key::Char('j') | Key::Down => self.state.day.next(),
+            Key::Char('k') | Key::Up => self.state.day.previous(),
+            Key::Char('m') => {
+                let selected = self.state.day.pos.selected();
+                let moving = selected.and_then(|i| {
+                    self.entries(self.state.cursor)
+                        .get(i)
+                        .map(|e| (e.item.index, e.due))
+                });
+                if moving.is_some() {
+                    self.state.moving = moving;
+                    self.state.focus = Focus::Grid;
+                }
+            }
+            Key::Char('w') => return Some(Action::Write),
+            Key::Char('W') => return Some(Action::ForceWrite),
+            _ => {}
+        }
+        None
+    }
+}
+
+fn entries<'a>(items: &'a [ParsedLine<'a>], day: NaiveDate) -> Vec<Entry<'a>> {
+    let on = |d: Option<&str>| d.and_then(date::from_iso) == Some(day);
+    let mut entries = Vec::new();
+    for item in items {
+        if on(item.due_date) {
+            entries.push(Entry { item, due: true });
+        }
+        if on(item.threshold_date) {
+            entries.push(Entry { item, due: false });
+        }
+    }
+    entries
+}
+
+fn border_style(focus: Focus, pane: Focus) -> Style {
+    if focus == pane {
+        Style::default().fg(Color::White)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    }
+}
+
+fn start_style() -> Style {
+    Style::default().fg(Color::Blue)
+}
+
+fn entry_line<'a>(entry: &Entry<'a>) -> Spans<'a> {
+    let style = if entry.item.complete {
+        Style::default()
+            .fg(Color::DarkGray)
+            .add_modifier(Modifier::CROSSED_OUT)
+    } else if entry.due {
+        Style::default()
+    } else {
+        start_style()
+    };
+    let marker = if entry.due { "• " } else { "▸ " };
+
+    Spans::from(vec![
+        Span::styled(marker, style),
+        Span::styled(&entry.item.body[..], style),
+    ])
+}
+
+impl<'a> View for CalendarView<'a> {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let outer_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Min(0), Length(1)].as_ref())
+            .split(f.size());
+
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Percentage(75), Percentage(25)].as_ref())
+            .split(outer_chunks[0]);
+
+        self.draw_grid(f, chunks[0]);
+        self.draw_day_list(f, chunks[1]);
+        self.draw_status_bar(f, outer_chunks[1]);
+    }
+}
+
+impl<'a> EventHandler<Key> for CalendarView<'a> {
+    fn handle(&mut self, event: Event<Key>) -> Option<Action> {
+        match event {
+            Event::Input(key) => {
+                *self.status = None;
+                match self.state.focus {
+                    Focus::Grid => self.handle_grid(key),
+                    Focus::Day => self.handle_day(key),
+                }
+            }
+            Event::Tick => {
+                *self.today = self.clock.today();
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,515 @@
+use crate::{
+    addons,
+    config::{self, Config},
+    error::Error,
+    query::Query,
+    todo::{
+        date::{Clock, SystemClock},
+        backup::Backups,
+        edit, ListHandle, ParsedLine, LOCK_WAIT,
+    },
+};
+
+use clap::{ArgMatches, Values};
+use std::{collections::BTreeSet, env, path::Path, process};
+
+/// the commands which change the files, and so hold the lock on the todo
+/// file from reading it to writing it back
+const WRITERS: &[&str] = &[
+    "add", "do", "undo", "del", "pri", "depri", "append", "prepend", "replace", "archive",
+];
+
+/// `run` carries out the subcommand `name` on the todo file without starting
+/// the interface. Tasks are numbered by line from 1 and changes are printed
+/// in the same format as todo.sh, so scripts written against it keep working.
+/// Commands which aren't built in are run as todo.sh add-ons, as are those
+/// which are but have an add-on of the same name, as todo.sh allows.
+pub fn run(name: &str, args: &ArgMatches) -> Result<(), Error> {
+    // before taking the lock, as the add-on may well run a command which does
+    if let Some((action, rest)) = command_line().filter(|(action, _)| addons::exists(action)) {
+        let rest: Vec<&str> = rest.iter().map(String::as_str).collect();
+        return run_addon(&action, &rest);
+    }
+
+    let dir = config::todo_dir();
+    let todo_path = dir.join(config::TODO_FILE);
+    let backups = Config::load()?.backups.backups();
+    let list_handle = ListHandle::new(&todo_path).backed_up(&backups);
+    let _lock = if WRITERS.contains(&name) {
+        Some(list_handle.lock(LOCK_WAIT)?)
+    } else {
+        None
+    };
+    let mut tasks = if list_handle.exists() {
+        list_handle.get_lines()?
+    } else {
+        Vec::new()
+    };
+    let before = tasks.clone();
+    let clock = SystemClock;
+
+    let output = match name {
+        "add" => add(&mut tasks, &text(args), &clock)?,
+        "list" => list(&tasks, args.values_of("TERM")),
+        "do" => for_items(&mut tasks, &numbers(args), |n, task| complete(n, task, &clock))?,
+        "undo" => for_items(&mut tasks, &numbers(args), |n, task| reopen(n, task, &clock))?,
+        "del" => delete(&mut tasks, args)?,
+        "pri" => prioritise(&mut tasks, args)?,
+        "depri" => for_items(&mut tasks, &numbers(args), deprioritise)?,
+        "append" => {
+            let (n, task) = item(&mut tasks, args)?;
+            *task = format!("{} {}", task, ParsedLine::expand_dates(&text(args), &clock));
+            vec![format!("{} {}", n, task)]
+        }
+        "prepend" => {
+            let (n, task) = item(&mut tasks, args)?;
+            *task = prepend(task, &ParsedLine::expand_dates(&text(args), &clock));
+            vec![format!("{} {}", n, task)]
+        }
+        "replace" => {
+            let (n, task) = item(&mut tasks, args)?;
+            let old = format!("{} {}", n, task);
+            *task = ParsedLine::expand_dates(&text(args), &clock);
+            vec![
+                old,
+                "TODO: Replaced task with:".to_owned(),
+                format!("{} {}", n, task),
+            ]
+        }
+        "archive" => {
+            let done_path = dir.join(config::DONE_FILE);
+            archive(&mut tasks, &done_path, &todo_path, &backups)?
+        }
+        "listcon" => list_attributes(&tasks, |item| &item.contexts),
+        "listproj" => list_attributes(&tasks, |item| &item.tags),
+        _ => {
+            let args: Vec<&str> = args.values_of("").into_iter().flatten().collect();
+            return run_addon(name, &args);
+        }
+    };
+
+    if tasks != before {
+        list_handle.write(&tasks)?;
+    }
+    for line in output {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// `command_line` is the command as it was typed, which may be an alias of
+/// the one it was taken for, followed by its arguments
+fn command_line() -> Option<(String, Vec<String>)> {
+    let mut args = env::args_os().skip(1).map(|a| a.to_string_lossy().into_owned());
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--view" => {
+                args.next();
+            }
+            "--report" => {
+                args.nth(1);
+            }
+            _ if arg.starts_with('-') => {}
+            _ => return Some((arg, args.collect())),
+        }
+    }
+    None
+}
+
+/// `run_addon` runs the add-on `action`, exiting with its status if it fails
+fn run_addon(action: &str, args: &[&str]) -> Result<(), Error> {
+    match addons::run(action, args)? {
+        0 => Ok(()),
+        code => process::exit(code),
+    }
+}
+
+fn text(args: &ArgMatches) -> String {
+    args.values_of("TEXT")
+        .map(|words| words.collect::<Vec<&str>>().join(" "))
+        .unwrap_or_default()
+}
+
+/// `find` is the task numbered `arg`, as long as the line isn't blank
+fn find<'a>(tasks: &'a mut [String], arg: &str) -> Result<(usize, &'a mut String), String> {
+    let n: usize = arg
+        .parse()
+        .map_err(|_| format!("TODO: {} is not a task number.", arg))?;
+    let i = n
+        .checked_sub(1)
+        .filter(|&i| tasks.get(i).is_some_and(|task| !task.trim().is_empty()))
+        .ok_or_else(|| format!("TODO: No task {}.", n))?;
+    Ok((n, &mut tasks[i]))
+}
+
+fn item<'a>(tasks: &'a mut [String], args: &ArgMatches) -> Result<(usize, &'a mut String), String> {
+    find(tasks, args.value_of("ITEM").unwrap_or_default())
+}
+
+fn numbers<'a>(args: &'a ArgMatches) -> Vec<&'a str> {
+    args.values_of("ITEM").into_iter().flatten().collect()
+}
+
+/// `for_items` applies `f` to each task numbered in `numbers`. Like todo.sh
+/// every number is checked before any task is changed, and nothing is
+/// changed unless `f` succeeds on all of them.
+fn for_items<F>(tasks: &mut [String], numbers: &[&str], f: F) -> Result<Vec<String>, String>
+where
+    F: Fn(usize, &mut String) -> Result<Vec<String>, String>,
+{
+    let numbers = numbers
+        .iter()
+        .map(|arg| find(tasks, arg).map(|(n, _)| n))
+        .collect::<Result<Vec<usize>, String>>()?;
+
+    let mut changed = tasks.to_vec();
+    let mut output = Vec::new();
+    for n in numbers {
+        output.extend(f(n, &mut changed[n - 1])?);
+    }
+    tasks.clone_from_slice(&changed);
+    Ok(output)
+}
+
+fn add(tasks: &mut Vec<String>, text: &str, clock: &dyn Clock) -> Result<Vec<String>, String> {
+    if text.trim().is_empty() {
+        return Err("usage: todotui-rs add \"TODO ITEM\"".to_owned());
+    }
+    tasks.push(ParsedLine::expand_dates(text, clock));
+    let n = tasks.len();
+    Ok(vec![
+        format!("{} {}", n, tasks[n - 1]),
+        format!("TODO: {} added.", n),
+    ])
+}
+
+/// `list` shows the tasks matching the terms, sorted by their text
+fn list(tasks: &[String], terms: Option<Values>) -> Vec<String> {
+    let query = Query::new(terms.into_iter().flatten());
+    let mut shown: Vec<(usize, &String)> = tasks
+        .iter()
+        .enumerate()
+        .filter(|(_, task)| !task.trim().is_empty() && query.matches(task))
+        .map(|(i, task)| (i + 1, task))
+        .collect();
+    shown.sort_by_key(|&(n, task)| (task.to_lowercase(), n));
+
+    let width = tasks.len().to_string().len();
+    let total = tasks.iter().filter(|task| !task.trim().is_empty()).count();
+    let mut output: Vec<String> = shown
+        .iter()
+        .map(|(n, task)| format!("{:0width$} {}", n, task, width = width))
+        .collect();
+    output.push("--".to_owned());
+    output.push(format!("TODO: {} of {} tasks shown", shown.len(), total));
+    output
+}
+
+/// `complete` marks a task done, dropping its priority as todo.sh does
+fn complete(n: usize, task: &mut String, clock: &dyn Clock) -> Result<Vec<String>, String> {
+    if task.starts_with("x ") {
+        return Err(format!("TODO: {} is already marked done.", n));
+    }
+    *task = edit::toggle_complete(&edit::set_priority(task, None), clock.today());
+    Ok(vec![
+        format!("{} {}", n, task),
+        format!("TODO: {} marked as done.", n),
+    ])
+}
+
+fn reopen(n: usize, task: &mut String, clock: &dyn Clock) -> Result<Vec<String>, String> {
+    if !task.starts_with("x ") {
+        return Err(format!("TODO: {} is not marked done.", n));
+    }
+    *task = edit::toggle_complete(task, clock.today());
+    Ok(vec![
+        format!("{} {}", n, task),
+        format!("TODO: {} marked as not done.", n),
+    ])
+}
+
+/// `delete` blanks the line of a task, so the tasks after it keep their
+/// numbers until the next archive. With a term only that's taken out.
+fn delete(tasks: &mut [String], args: &ArgMatches) -> Result<Vec<String>, String> {
+    let (n, task) = item(tasks, args)?;
+    let Some(term) = args.value_of("TERM") else {
+        let old = format!("{} {}", n, task);
+        task.clear();
+        return Ok(vec![old, format!("TODO: {} deleted.", n)]);
+    };
+
+    if !task.contains(term) {
+        return Ok(vec![
+            format!("{} {}", n, task),
+            format!("TODO: '{}' not found; no removal done.", term),
+        ]);
+    }
+    *task = task
+        .replace(term, "")
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    Ok(vec![
+        format!("{} {}", n, task),
+        format!("TODO: Removed '{}' from task.", term),
+    ])
+}
+
+fn prioritise(tasks: &mut [String], args: &ArgMatches) -> Result<Vec<String>, String> {
+    let priority = args
+        .value_of("PRIORITY")
+        .map(str::to_uppercase)
+        .and_then(|p| match p.as_bytes() {
+            [c @ b'A'..=b'Z'] => Some(char::from(*c)),
+            _ => None,
+        })
+        .ok_or(
+            "usage: todotui-rs pri ITEM# PRIORITY\nnote: PRIORITY must be anywhere from A to Z.",
+        )?;
+
+    let (n, task) = item(tasks, args)?;
+    *task = edit::set_priority(task, Some(priority));
+    Ok(vec![
+        format!("{} {}", n, task),
+        format!("TODO: {} prioritized ({}).", n, priority),
+    ])
+}
+
+fn deprioritise(n: usize, task: &mut String) -> Result<Vec<String>, String> {
+    let prioritised = task
+        .split(' ')
+        .next()
+        .and_then(ParsedLine::is_priority)
+        .is_some();
+    if !prioritised {
+        return Err(format!("TODO: {} is not prioritized.", n));
+    }
+    *task = edit::set_priority(task, None);
+    Ok(vec![
+        format!("{} {}", n, task),
+        format!("TODO: {} deprioritized.", n),
+    ])
+}
+
+/// `prepend` puts `text` at the start of the task, after its priority and
+/// creation date
+fn prepend(task: &str, text: &str) -> String {
+    let mut words: Vec<&str> = task.split(' ').collect();
+    let mut at = 0;
+    if words
+        .first()
+        .and_then(|w| ParsedLine::is_priority(w))
+        .is_some()
+    {
+        at += 1;
+    }
+    if words.get(at).is_some_and(|w| ParsedLine::is_date(w)) {
+        at += 1;
+    }
+    words.insert(at, text);
+    words.join(" ")
+}
+
+/// `archive` moves the done tasks to the done file and drops blank lines,
+/// which numbers the tasks afresh. The done file is written before the tasks
+/// are taken out, so a failure can leave a task in both files but never in
+/// neither.
+fn archive(
+    tasks: &mut Vec<String>,
+    done_path: &Path,
+    todo_path: &Path,
+    backups: &Backups,
+) -> Result<Vec<String>, Error> {
+    let (finished, open): (Vec<String>, Vec<String>) = tasks
+        .iter()
+        .filter(|task| !task.trim().is_empty())
+        .cloned()
+        .partition(|task| task.starts_with("x "));
+
+    let done_handle = ListHandle::new(done_path).backed_up(backups);
+    if !finished.is_empty() {
+        let mut done = if done_handle.exists() {
+            done_handle.get_lines()?
+        } else {
+            Vec::new()
+        };
+        done.extend(finished.iter().cloned());
+        done_handle.write(&done)?;
+    }
+    *tasks = open;
+
+    let mut output = finished;
+    output.push(format!("TODO: {} archived.", todo_path.display()));
+    Ok(output)
+}
+
+/// `list_attributes` prints each context or project in use once
+fn list_attributes<F>(tasks: &[String], get: F) -> Vec<String>
+where
+    F: for<'a, 'b> Fn(&'b ParsedLine<'a>) -> &'b Vec<&'a str>,
+{
+    let mut names = BTreeSet::new();
+    for (i, task) in tasks.iter().enumerate() {
+        let item = ParsedLine::new(task, i);
+        names.extend(get(&item).iter().map(|&name| name.to_owned()));
+    }
+    names.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|&l| l.to_owned()).collect()
+    }
+
+    #[test]
+    fn find_numbers_from_one_skipping_blanks() {
+        let mut tasks = lines(&["call bob", "", "fix bike"]);
+        assert_eq!(
+            find(&mut tasks, "3").map(|(n, t)| (n, t.clone())),
+            Ok((3, "fix bike".to_owned()))
+        );
+        assert_eq!(
+            find(&mut tasks, "2").map(|(n, _)| n),
+            Err("TODO: No task 2.".to_owned())
+        );
+        assert_eq!(
+            find(&mut tasks, "0").map(|(n, _)| n),
+            Err("TODO: No task 0.".to_owned())
+        );
+        assert_eq!(
+            find(&mut tasks, "4").map(|(n, _)| n),
+            Err("TODO: No task 4.".to_owned())
+        );
+        assert_eq!(
+            find(&mut tasks, "x").map(|(n, _)| n),
+            Err("TODO: x is not a task number.".to_owned())
+        );
+    }
+
+    #[test]
+    fn add_numbers_the_new_line() {
+        let today = NaiveDate::from_ymd(2020, 10, 14);
+        let mut tasks = lines(&["call bob", ""]);
+        assert_eq!(
+            add(&mut tasks, "fix bike due:tom", &today),
+            Ok(lines(&["3 fix bike due:2020-10-15", "TODO: 3 added."]))
+        );
+        assert!(add(&mut tasks, " ", &today).is_err());
+    }
+
+    #[test]
+    fn list_sorts_by_text_keeping_numbers() {
+        let tasks = lines(&["b task", "", "A task"]);
+        assert_eq!(
+            list(&tasks, None),
+            lines(&["3 A task", "1 b task", "--", "TODO: 2 of 2 tasks shown"])
+        );
+    }
+
+    #[test]
+    fn list_pads_numbers_to_the_widest() {
+        let mut tasks = vec![String::new(); 9];
+        tasks.push("last".to_owned());
+        tasks[0] = "first".to_owned();
+        assert_eq!(
+            list(&tasks, None),
+            lines(&["01 first", "10 last", "--", "TODO: 2 of 2 tasks shown"])
+        );
+    }
+
+    #[test]
+    fn complete_and_reopen() {
+        let today = NaiveDate::from_ymd(2020, 10, 14);
+        let mut task = "(A) call bob".to_owned();
+        assert_eq!(
+            complete(1, &mut task, &today),
+            Ok(lines(&[
+                "1 x 2020-10-14 call bob",
+                "TODO: 1 marked as done."
+            ]))
+        );
+        assert!(complete(1, &mut task, &today).is_err());
+        assert_eq!(
+            reopen(1, &mut task, &today),
+            Ok(lines(&["1 call bob", "TODO: 1 marked as not done."]))
+        );
+        assert!(reopen(1, &mut task, &today).is_err());
+    }
+
+    #[test]
+    fn deprioritise_needs_a_priority() {
+        let mut task = "(B) call bob".to_owned();
+        assert_eq!(
+            deprioritise(2, &mut task),
+            Ok(lines(&["2 call bob", "TODO: 2 deprioritized."]))
+        );
+        assert_eq!(
+            deprioritise(2, &mut task),
+            Err("TODO: 2 is not prioritized.".to_owned())
+        );
+    }
+
+    #[test]
+    fn prepend_goes_after_priority_and_date() {
+        assert_eq!(
+            prepend("(A) 2020-10-01 call bob", "soon"),
+            "(A) 2020-10-01 soon call bob"
+        );
+        assert_eq!(prepend("call bob", "soon"), "soon call bob");
+    }
+
+    #[test]
+    fn list_attributes_once_each() {
+        let tasks = lines(&["a @home +x", "b @work @home", "c +x"]);
+        assert_eq!(
+            list_attributes(&tasks, |item| &item.contexts),
+            lines(&["@home", "@work"])
+        );
+        assert_eq!(list_attributes(&tasks, |item| &item.tags), lines(&["+x"]));
+    }
+
+    #[test]
+    fn for_items_changes_all_or_nothing() {
+        let mut tasks = lines(&["(A) call bob", "call alice"]);
+        assert_eq!(
+            for_items(&mut tasks, &["1", "3"], deprioritise),
+            Err("TODO: No task 3.".to_owned())
+        );
+        assert_eq!(
+            for_items(&mut tasks, &["1", "2"], deprioritise),
+            Err("TODO: 2 is not prioritized.".to_owned())
+        );
+        assert_eq!(tasks, lines(&["(A) call bob", "call alice"]));
+
+        for_items(&mut tasks, &["1"], deprioritise).unwrap();
+        assert_eq!(tasks, lines(&["call bob", "call alice"]));
+    }
+
+    #[test]
+    fn archive_keeps_the_tasks_until_theyre_in_the_done_file() {
+        let dir = std::env::temp_dir().join(format!("todotui-rs-{}-archive", process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let backups = Backups::new(dir.join("backups"), 0);
+        let todo_path = dir.join("todo.txt");
+        let before = lines(&["x 2020-10-14 call bob", "", "call alice"]);
+
+        let mut tasks = before.clone();
+        let missing = dir.join("missing/done.txt");
+        assert!(archive(&mut tasks, &missing, &todo_path, &backups).is_err());
+        assert_eq!(tasks, before);
+
+        let done_path = dir.join("done.txt");
+        archive(&mut tasks, &done_path, &todo_path, &backups).unwrap();
+        assert_eq!(tasks, lines(&["call alice"]));
+        assert_eq!(
+            ListHandle::new(&done_path).get_lines().unwrap(),
+            lines(&["x 2020-10-14 call bob"])
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
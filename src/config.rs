@@ -0,0 +1,325 @@
+use crate::{error::Error, hooks::Hook, sidebar::Order, todo::backup::Backups};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    NaiveDate,
+};
+use std::io::{prelude::*, BufReader};
+use std::{collections::HashMap, env, fmt::Write as _, fs::File, path::PathBuf};
+
+const APP_DIR: &str = "todotui-rs";
+const CONFIG_FILE: &str = "config";
+pub const TODO_FILE: &str = "main.todo.txt";
+pub const DONE_FILE: &str = "done.txt";
+
+/// Config holds the user settings read from `$XDG_CONFIG_HOME/todotui-rs/config`.
+///
+/// The file is made up of `section.key = value` lines, blank lines and lines
+/// starting with `#` are ignored. Any setting left out keeps its default.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    pub dates: DateConfig,
+    pub defer: DeferConfig,
+    pub board: BoardConfig,
+    pub tree: TreeConfig,
+    pub pomodoro: PomodoroConfig,
+    pub sidebar: SidebarConfig,
+    pub backups: BackupConfig,
+    /// shell commands run at each hook point, keyed by the hook's name
+    pub hooks: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DateConfig {
+    /// show dates relative to today ("due in 2 days") instead of absolute
+    pub relative: bool,
+    /// strftime format used for absolute dates
+    pub format: String,
+}
+
+impl Default for DateConfig {
+    fn default() -> Self {
+        Self {
+            relative: true,
+            format: "%Y-%m-%d".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DeferConfig {
+    /// move the due date along with the threshold when deferring
+    pub shift_due: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TreeConfig {
+    /// completing a task also completes everything nested under it
+    pub complete_children: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct PomodoroConfig {
+    /// minutes in each interval
+    pub work: i64,
+    pub short_break: i64,
+    pub long_break: i64,
+    /// how many work intervals come before a long break
+    pub long_every: u32,
+    /// shell command run at each transition instead of ringing the bell, with
+    /// `POMODORO_PHASE` set to the phase starting
+    pub command: Option<String>,
+}
+
+impl Default for PomodoroConfig {
+    fn default() -> Self {
+        Self {
+            work: 25,
+            short_break: 5,
+            long_break: 15,
+            long_every: 4,
+            command: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SidebarConfig {
+    /// only list the contexts and tags found alongside the active filters
+    pub faceted: bool,
+    pub order: Order,
+    /// splits names like `+work.infra` into a tree, nothing if empty
+    pub separator: String,
+}
+
+impl Default for SidebarConfig {
+    fn default() -> Self {
+        Self {
+            faceted: false,
+            order: Order::Name,
+            separator: ".".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    /// how many copies of each file are kept from before it was written
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self { keep: 5 }
+    }
+}
+
+impl BackupConfig {
+    /// `backups` is where the todo and done files are backed up
+    pub fn backups(&self) -> Backups {
+        Backups::new(state_dir().join("backups"), self.keep)
+    }
+}
+
+/// the board key which lays out columns by priority rather than an extension
+pub const PRIORITY_KEY: &str = "priority";
+
+#[derive(Debug, Clone)]
+pub struct BoardConfig {
+    /// extension key holding a task's column, or `priority`
+    pub key: String,
+    /// column values in order, `-` stands for tasks without a priority
+    pub columns: Option<Vec<String>>,
+    /// the most cards allowed in a column
+    pub wip: HashMap<String, usize>,
+}
+
+impl Default for BoardConfig {
+    fn default() -> Self {
+        Self {
+            key: "status".to_owned(),
+            columns: None,
+            wip: HashMap::new(),
+        }
+    }
+}
+
+impl BoardConfig {
+    pub fn columns(&self) -> Vec<String> {
+        if let Some(columns) = self.columns.as_ref().filter(|c| !c.is_empty()) {
+            return columns.clone();
+        }
+        let defaults: &[&str] = if self.key == PRIORITY_KEY {
+            &["A", "B", "C", "-"]
+        } else {
+            &["todo", "doing", "done"]
+        };
+        defaults.iter().map(|&c| c.to_owned()).collect()
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Config, Error> {
+        let mut config = Config::default();
+        let path = dir().join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(config);
+        }
+
+        let buf_reader = BufReader::new(File::open(&path).map_err(Error::io(&path))?);
+        for (i, line) in buf_reader.lines().enumerate() {
+            let line = line.map_err(Error::io(&path))?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let things: Vec<&str> = line.splitn(2, '=').map(str::trim).collect();
+            let res = match things[..] {
+                [key, value] => config.set(key, value),
+                _ => Err("expected `key = value`".to_owned()),
+            };
+
+            if let Err(message) = res {
+                return Err(Error::Config {
+                    path,
+                    line: i + 1,
+                    message,
+                });
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "dates.relative" => self.dates.relative = parse_bool(value)?,
+            "dates.format" => self.dates.format = parse_format(value)?,
+            "defer.shift_due" => self.defer.shift_due = parse_bool(value)?,
+            "tree.complete_children" => self.tree.complete_children = parse_bool(value)?,
+            "pomodoro.work" => self.pomodoro.work = parse_minutes(value)?,
+            "pomodoro.break" => self.pomodoro.short_break = parse_minutes(value)?,
+            "pomodoro.long_break" => self.pomodoro.long_break = parse_minutes(value)?,
+            "pomodoro.long_every" => {
+                self.pomodoro.long_every = value
+                    .parse()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("expected a positive number, got {}", value))?;
+            }
+            "pomodoro.command" => self.pomodoro.command = Some(value.to_owned()),
+            "sidebar.faceted" => self.sidebar.faceted = parse_bool(value)?,
+            "sidebar.order" => {
+                self.sidebar.order = Order::from_name(value).ok_or_else(|| {
+                    format!("expected name, count or recent, got {}", value)
+                })?;
+            }
+            "sidebar.separator" => value.clone_into(&mut self.sidebar.separator),
+            "backups.keep" => {
+                self.backups.keep = value
+                    .parse()
+                    .map_err(|_| format!("expected a number, got {}", value))?;
+            }
+            "board.key" => value.clone_into(&mut self.board.key),
+            "board.columns" => self.board.columns = Some(parse_list(value)),
+            _ if key.starts_with("board.wip.") => {
+                let limit = value
+                    .parse()
+                    .map_err(|_| format!("expected a number, got {}", value))?;
+                self.board.wip.insert(key["board.wip.".len()..].to_owned(), limit);
+            }
+            _ if key.starts_with("hooks.") => {
+                let name = &key["hooks.".len()..];
+                if Hook::from_name(name).is_none() {
+                    return Err(format!("unknown hook {}", name));
+                }
+                self.hooks.insert(name.to_owned(), value.to_owned());
+            }
+            _ => return Err(format!("unknown setting {}", key)),
+        }
+        Ok(())
+    }
+}
+
+/// `parse_list` splits a list of values separated by `|` or `,`
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(['|', ','].as_ref())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn parse_minutes(value: &str) -> Result<i64, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("expected a number of minutes, got {}", value))
+}
+
+/// `parse_format` checks a strftime format up front, as one chrono can't
+/// follow, or one asking for a time or offset a date doesn't have, only
+/// fails once it's used to format a date
+fn parse_format(value: &str) -> Result<String, String> {
+    let invalid = || format!("invalid date format {}", value);
+    if StrftimeItems::new(value).any(|item| item == Item::Error) {
+        return Err(invalid());
+    }
+    // written out rather than with `to_string`, which panics on the error
+    let date = NaiveDate::from_ymd_opt(2020, 1, 1).ok_or_else(invalid)?;
+    write!(String::new(), "{}", date.format(value)).map_err(|_| invalid())?;
+    Ok(value.to_owned())
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!("expected a boolean, got {}", value)),
+    }
+}
+
+/// `todo_dir` is the directory holding the todo and done files. Like
+/// todo.sh it's read from `$TODO_DIR`, falling back on `~/.todo`.
+pub fn todo_dir() -> PathBuf {
+    env::var_os("TODO_DIR")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".todo")))
+        .unwrap_or_default()
+}
+
+/// `state_dir` is the directory holding files the program keeps for itself
+/// between runs, `$XDG_STATE_HOME/todotui-rs`
+pub fn state_dir() -> PathBuf {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+        .unwrap_or_default()
+        .join(APP_DIR)
+}
+
+/// `dir` is the directory holding the config file and any other user
+/// provided files
+pub fn dir() -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .unwrap_or_default()
+        .join(APP_DIR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_formats() {
+        assert_eq!(parse_format("%d/%m/%Y").unwrap(), "%d/%m/%Y");
+        assert!(parse_format("%Y-%Q").is_err());
+        assert!(parse_format("%H:%M").is_err());
+        assert!(parse_format("%Y %z").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// `Error` is anything which can go wrong reading, parsing or writing the
+/// todo files and the files kept alongside them
+#[derive(Debug)]
+pub enum Error {
+    /// reading or writing a file failed, or the terminal or another program
+    /// when there's no file involved
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+    /// a line of a file couldn't be understood
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// a setting in the config file is invalid
+    Config {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    /// the file was changed by something else since it was read, so writing
+    /// it would lose those changes
+    Conflict { path: PathBuf },
+    /// another process holds the lock on the file, naming it if it could
+    Locked { path: PathBuf, pid: Option<u32> },
+    /// the file was opened without being allowed to write it
+    ReadOnly { path: PathBuf, reason: String },
+    /// a command was given something it can't act on
+    Usage(String),
+}
+
+impl Error {
+    /// `io` ties an I/O error to the file it happened on, for use with
+    /// `map_err`
+    pub fn io(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+        move |source| Error::Io {
+            path: Some(path.to_owned()),
+            source,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {}", path.display(), source),
+            Error::Io { path: None, source } => write!(f, "{}", source),
+            Error::Parse {
+                path,
+                line,
+                message,
+            }
+            | Error::Config {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path.display(), line, message),
+            Error::Conflict { path } => write!(
+                f,
+                "{} was changed by another program since it was read",
+                path.display()
+            ),
+            Error::Locked { path, pid: Some(pid) } => write!(
+                f,
+                "{} is locked by another process (pid {})",
+                path.display(),
+                pid
+            ),
+            Error::Locked { path, pid: None } => {
+                write!(f, "{} is locked by another process", path.display())
+            }
+            Error::ReadOnly { path, reason } => {
+                write!(f, "{} is open read only: {}", path.display(), reason)
+            }
+            Error::Usage(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::Io { path: None, source }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Usage(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::Usage(message.to_owned())
+    }
+}
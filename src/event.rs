@@ -14,7 +14,7 @@ pub trait Handler<I> {
 
 pub enum Event<I> {
     Input(I),
-    _Tick,
+    Tick,
 }
 
 pub struct Generator {
@@ -24,14 +24,12 @@ pub struct Generator {
 
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
-    pub exit_key: Key,
     pub tick_rate: Duration,
 }
 
 impl Default for Config {
     fn default() -> Config {
         Config {
-            exit_key: Key::Char('q'),
             tick_rate: Duration::from_millis(250),
         }
     }
@@ -44,14 +42,24 @@ impl Generator {
 
     pub fn with_config(config: Config) -> Generator {
         let (sender, receiver) = mpsc::channel();
+
+        let tick_sender = sender.clone();
+        thread::spawn(move || loop {
+            thread::sleep(config.tick_rate);
+            if tick_sender.send(Event::Tick).is_err() {
+                return;
+            }
+        });
+
         //let input_handle = {
         //let sender = sender.clone();
         thread::spawn(move || {
             let stdin = io::stdin();
             for evt in stdin.keys() {
+                // keys are forwarded until the receiver goes away, since the
+                // exit key may also just be text typed into a prompt
                 if let Ok(key) = evt {
-                    sender.send(Event::Input(key)).unwrap();
-                    if key == config.exit_key {
+                    if sender.send(Event::Input(key)).is_err() {
                         return;
                     }
                 }
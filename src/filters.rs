@@ -1,7 +1,5 @@
 use std::collections::BTreeSet;
 
-use crate::app::ActiveList;
-
 /// Filters is meant to be a struct which holds filters
 pub struct Filters<I>
 where
@@ -11,6 +9,13 @@ where
     pub tags: I,
 }
 
+/// `Kind` is which of the two attributes of a task a filter is on
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Contexts,
+    Tags,
+}
+
 impl<I> Filters<I>
 where
     I: IntoIterator,
@@ -19,31 +24,31 @@ where
         Self { contexts, tags }
     }
 
-    pub fn get_mut(&mut self, al: ActiveList) -> &mut I {
-        match al {
-            ActiveList::Contexts => &mut self.contexts,
-            ActiveList::Tags => &mut self.tags,
-            ActiveList::Tasks => panic!("tasks is not a filter"),
+    pub fn get_mut(&mut self, kind: Kind) -> &mut I {
+        match kind {
+            Kind::Contexts => &mut self.contexts,
+            Kind::Tags => &mut self.tags,
         }
     }
 
-    pub fn get(&self, al: ActiveList) -> &I {
-        match al {
-            ActiveList::Contexts => &self.contexts,
-            ActiveList::Tags => &self.tags,
-            ActiveList::Tasks => panic!("tasks is not a filter"),
+    pub fn get(&self, kind: Kind) -> &I {
+        match kind {
+            Kind::Contexts => &self.contexts,
+            Kind::Tags => &self.tags,
         }
     }
 }
 
 impl<'a> Filters<BTreeSet<&'a str>> {
+    /// `include` is true when `item` has one of the contexts filtered on, if
+    /// there are any, and likewise one of the tags
+    #[must_use]
     pub fn include(&self, item: &str) -> bool {
-        self.include_for_filter(item, ActiveList::Contexts)
-            && self.include_for_filter(item, ActiveList::Tags)
+        self.include_for_filter(item, Kind::Contexts) && self.include_for_filter(item, Kind::Tags)
     }
 
-    fn include_for_filter(&self, item: &str, active_list: ActiveList) -> bool {
-        let filters = self.get(active_list);
+    fn include_for_filter(&self, item: &str, kind: Kind) -> bool {
+        let filters = self.get(kind);
         if filters.is_empty() {
             return true;
         }
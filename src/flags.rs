@@ -1,9 +1,116 @@
-
-use clap::{App, crate_authors, crate_name, crate_version};
+use clap::{App, AppSettings, Arg, SubCommand, crate_name, crate_version};
 
 pub fn parse() -> clap::ArgMatches<'static> {
     App::new(crate_name!())
         .version(crate_version!())
-        .author(crate_authors!(", "))
+        // not crate_authors!, whose expansion in clap 2.33 no longer
+        // builds on current rustc (dangerous_implicit_autorefs)
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .setting(AppSettings::VersionlessSubcommands)
+        // any other command is looked for among the todo.sh add-ons
+        .setting(AppSettings::AllowExternalSubcommands)
+        .arg(
+            Arg::with_name("view")
+                .long("view")
+                .takes_value(true)
+                .possible_values(&[
+                    "main", "calendar", "agenda", "board", "stats", "projects", "log",
+                ])
+                .default_value("main")
+                .help("The view to open on start"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .global(true)
+                .help("Log more, once for debug and twice for trace, instead of $TODOTUI_LOG"),
+        )
+        .arg(
+            Arg::with_name("report")
+                .long("report")
+                .number_of_values(2)
+                .allow_hyphen_values(true)
+                .value_names(&["FROM", "TO"])
+                .help("Print the time logged per project and context between two dates"),
+        )
+        .subcommand(
+            SubCommand::with_name("add")
+                .visible_alias("a")
+                .setting(AppSettings::AllowLeadingHyphen)
+                .about("Add a task")
+                .arg(text().required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("list")
+                .visible_alias("ls")
+                .setting(AppSettings::AllowLeadingHyphen)
+                .about("List the tasks containing every TERM and none of the -TERMs")
+                .arg(
+                    Arg::with_name("TERM")
+                        .multiple(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(items("do", "Mark tasks as done"))
+        .subcommand(items("undo", "Mark done tasks as not done"))
+        .subcommand(
+            SubCommand::with_name("del")
+                .visible_alias("rm")
+                .setting(AppSettings::AllowLeadingHyphen)
+                .about("Delete a task, or only TERM from it")
+                .arg(item())
+                .arg(Arg::with_name("TERM").allow_hyphen_values(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("pri")
+                .visible_alias("p")
+                .about("Give a task a priority")
+                .arg(item())
+                .arg(Arg::with_name("PRIORITY").required(true)),
+        )
+        .subcommand(items("depri", "Remove the priority of tasks").visible_alias("dp"))
+        .subcommand(edit("append", "Add text to the end of a task").visible_alias("app"))
+        .subcommand(edit("prepend", "Add text to the start of a task").visible_alias("prep"))
+        .subcommand(edit("replace", "Replace a task"))
+        .subcommand(SubCommand::with_name("archive").about("Move done tasks to the done file"))
+        .subcommand(
+            SubCommand::with_name("listcon")
+                .visible_alias("lsc")
+                .about("List the contexts in use"),
+        )
+        .subcommand(
+            SubCommand::with_name("listproj")
+                .visible_alias("lsprj")
+                .about("List the projects in use"),
+        )
         .get_matches()
 }
+
+/// `item` is the task number argument, counted from 1 as todo.sh does
+fn item() -> Arg<'static, 'static> {
+    Arg::with_name("ITEM").required(true)
+}
+
+fn text() -> Arg<'static, 'static> {
+    Arg::with_name("TEXT")
+        .multiple(true)
+        .allow_hyphen_values(true)
+}
+
+/// `items` is a subcommand acting on any number of tasks
+fn items(name: &'static str, about: &'static str) -> App<'static, 'static> {
+    SubCommand::with_name(name)
+        .about(about)
+        .arg(item().multiple(true))
+}
+
+/// `edit` is a subcommand rewriting a task with the text given
+fn edit(name: &'static str, about: &'static str) -> App<'static, 'static> {
+    SubCommand::with_name(name)
+        .about(about)
+        .setting(AppSettings::AllowLeadingHyphen)
+        .arg(item())
+        .arg(text().required(true))
+}
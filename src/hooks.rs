@@ -0,0 +1,317 @@
+use crate::{addons, config};
+
+use log::{debug, info};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// how long a hook or add-on may run before it's killed, as the interface
+/// waits on it
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `Hook` is a point in the life of the todo file where a user command can
+/// be run. The ones before a change can refuse it and some can rewrite the
+/// lines, the rest are only told about it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Hook {
+    PreWrite,
+    PostWrite,
+    Add,
+    Complete,
+    Delete,
+    Reload,
+}
+
+impl Hook {
+    const ALL: [Hook; 6] = [
+        Hook::PreWrite,
+        Hook::PostWrite,
+        Hook::Add,
+        Hook::Complete,
+        Hook::Delete,
+        Hook::Reload,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PreWrite => "pre-write",
+            Hook::PostWrite => "post-write",
+            Hook::Add => "on-add",
+            Hook::Complete => "on-complete",
+            Hook::Delete => "on-delete",
+            Hook::Reload => "on-reload",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Hook> {
+        Hook::ALL.iter().copied().find(|hook| hook.name() == name)
+    }
+
+    /// whether a failing hook stops the change from being made
+    fn vetoes(self) -> bool {
+        !matches!(self, Hook::PostWrite | Hook::Reload)
+    }
+
+    /// whether what the hook prints replaces the lines it was given
+    fn rewrites(self) -> bool {
+        matches!(self, Hook::PreWrite | Hook::Add | Hook::Complete)
+    }
+}
+
+/// `hooks_dir` holds executables named after the hooks, used for any hook
+/// without a command in the config
+fn hooks_dir() -> PathBuf {
+    config::dir().join("hooks")
+}
+
+/// `command` is the configured shell command for `hook`, otherwise the
+/// executable of the same name in the hooks directory
+fn command(hook: Hook, commands: &HashMap<String, String>) -> Option<Command> {
+    let mut command = if let Some(line) = commands.get(hook.name()) {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(line);
+        command
+    } else {
+        let path = hooks_dir().join(hook.name());
+        if !addons::is_executable(&path) {
+            return None;
+        }
+        Command::new(path)
+    };
+
+    let dir = config::todo_dir();
+    command
+        .env("TODOTUI_HOOK", hook.name())
+        .env("TODO_DIR", &dir)
+        .env("TODO_FILE", dir.join(config::TODO_FILE))
+        .env("DONE_FILE", dir.join(config::DONE_FILE));
+    Some(command)
+}
+
+/// `run` runs the command for `hook`, if there is one, with
+/// `{"hook": name, "lines": [...]}` on its stdin, and returns the lines the
+/// change should go ahead with.
+///
+/// A hook refuses a change by exiting with a non-zero status, the last line
+/// of its stderr being the reason given. Hooks which may rewrite the lines do
+/// so by printing `{"lines": [...]}`, with as many lines as they were given
+/// except for `pre-write`, which gets the whole todo file. Printing nothing
+/// leaves the lines as they were. A hook still running after `TIMEOUT` is
+/// killed and fails.
+pub fn run(
+    hook: Hook,
+    commands: &HashMap<String, String>,
+    lines: Vec<String>,
+) -> Result<Vec<String>, String> {
+    run_within(hook, commands, lines, TIMEOUT)
+}
+
+/// `run_within` is `run` with the hook killed after `timeout`
+fn run_within(
+    hook: Hook,
+    commands: &HashMap<String, String>,
+    lines: Vec<String>,
+    timeout: Duration,
+) -> Result<Vec<String>, String> {
+    let Some(mut command) = command(hook, commands) else {
+        return Ok(lines);
+    };
+    let failed = |reason: String| format!("{} hook failed: {}", hook.name(), reason);
+    debug!("running the {} hook: {:?}", hook.name(), command);
+
+    let input = json!({ "hook": hook.name(), "lines": &lines }).to_string();
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = output_within(&mut command, Some(input.into_bytes()), timeout)
+        .map_err(|e| failed(e.to_string()))?
+        .ok_or_else(|| failed(killed(timeout)))?;
+
+    if !status.success() {
+        let reason = String::from_utf8_lossy(&stderr)
+            .lines()
+            .rev()
+            .find(|l| !l.trim().is_empty())
+            .map_or_else(|| status.to_string(), str::to_owned);
+        return Err(if hook.vetoes() {
+            format!("{} hook refused: {}", hook.name(), reason)
+        } else {
+            failed(reason)
+        });
+    }
+
+    let stdout = String::from_utf8_lossy(&stdout);
+    if !hook.rewrites() || stdout.trim().is_empty() {
+        return Ok(lines);
+    }
+    info!("the {} hook rewrote the lines", hook.name());
+    let rewritten =
+        parse_lines(&stdout).ok_or_else(|| failed("expected {\"lines\": [...]}".to_owned()))?;
+    if hook != Hook::PreWrite && rewritten.len() != lines.len() {
+        return Err(failed(format!(
+            "expected {} lines, got {}",
+            lines.len(),
+            rewritten.len()
+        )));
+    }
+    Ok(rewritten)
+}
+
+/// `output_within` runs `command` with `input` on its stdin, or with none,
+/// collecting what it prints. It's killed if it's still running after
+/// `timeout`, which gives `None`.
+pub fn output_within(
+    command: &mut Command,
+    input: Option<Vec<u8>>,
+    timeout: Duration,
+) -> io::Result<Option<Output>> {
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // written from another thread so a command printing before it's read
+    // everything can't leave both sides waiting on full pipes
+    let stdin = child.stdin.take();
+    let writer = thread::spawn(move || {
+        if let (Some(mut stdin), Some(input)) = (stdin, input) {
+            // a command needn't read its input
+            let _ = stdin.write_all(&input);
+        }
+    });
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait()? {
+            Some(status) => break status,
+            None if started.elapsed() < timeout => thread::sleep(Duration::from_millis(20)),
+            None => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Ok(None);
+            }
+        }
+    };
+    let _ = writer.join();
+    Ok(Some(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    }))
+}
+
+/// `killed` is why a command which ran past `timeout` failed
+pub fn killed(timeout: Duration) -> String {
+    format!(
+        "still running after {}s, so it was killed",
+        timeout.as_secs_f32()
+    )
+}
+
+/// `read_all` reads everything from `pipe` on another thread, so the command
+/// can be waited on while it writes
+fn read_all<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// `parse_lines` reads the lines printed by a hook, which must each be a
+/// single line of text
+fn parse_lines(output: &str) -> Option<Vec<String>> {
+    let value: Value = serde_json::from_str(output).ok()?;
+    value
+        .get("lines")?
+        .as_array()?
+        .iter()
+        .map(|line| {
+            line.as_str()
+                .filter(|l| !l.contains('\n'))
+                .map(str::to_owned)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `run_sh` runs `line` as the command for `hook` over `lines`
+    fn run_sh(hook: Hook, line: &str, lines: &[&str]) -> Result<Vec<String>, String> {
+        let commands = vec![(hook.name().to_owned(), line.to_owned())]
+            .into_iter()
+            .collect();
+        let lines = lines.iter().map(|&l| l.to_owned()).collect();
+        run_within(hook, &commands, lines, Duration::from_millis(500))
+    }
+
+    #[test]
+    fn failing_refuses_the_change() {
+        assert_eq!(
+            run_sh(Hook::Add, "echo not today >&2; exit 1", &["call bob"]),
+            Err("on-add hook refused: not today".to_owned())
+        );
+        assert!(run_sh(Hook::PostWrite, "exit 1", &["call bob"])
+            .unwrap_err()
+            .starts_with("post-write hook failed"));
+    }
+
+    #[test]
+    fn printed_lines_replace_the_lines() {
+        let print = r#"cat >/dev/null; echo '{"lines": ["call bob +phone"]}'"#;
+        assert_eq!(
+            run_sh(Hook::Add, print, &["call bob"]),
+            Ok(vec!["call bob +phone".to_owned()])
+        );
+        assert_eq!(
+            run_sh(Hook::Add, "cat >/dev/null", &["call bob"]),
+            Ok(vec!["call bob".to_owned()])
+        );
+        // only told about the change, so what it prints is ignored
+        assert_eq!(
+            run_sh(Hook::Reload, print, &["call bob"]),
+            Ok(vec!["call bob".to_owned()])
+        );
+    }
+
+    #[test]
+    fn only_pre_write_changes_the_line_count() {
+        let print = r#"echo '{"lines": ["a", "b"]}'"#;
+        assert_eq!(
+            run_sh(Hook::Complete, print, &["a"]),
+            Err("on-complete hook failed: expected 1 lines, got 2".to_owned())
+        );
+        assert_eq!(
+            run_sh(Hook::PreWrite, print, &["a"]),
+            Ok(vec!["a".to_owned(), "b".to_owned()])
+        );
+    }
+
+    #[test]
+    fn a_hook_running_too_long_is_killed() {
+        let started = Instant::now();
+        assert_eq!(
+            run_sh(Hook::Add, "sleep 5", &["call bob"]),
+            Err("on-add hook failed: still running after 0.5s, so it was killed".to_owned())
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,21 @@
+//! Reading, parsing and searching todo.txt files, as used by the todotui-rs
+//! interface and its todo.sh compatible commands.
+//!
+//! A line of the todo file is parsed into a [`ParsedLine`], and the todo and
+//! done files are loaded and written together through [`ListRep`] and
+//! [`ListHandle`]. [`Filters`] select tasks by their contexts and projects,
+//! and a [`Query`] searches them by text. Everything which can fail does so
+//! with an [`Error`].
+
+#![warn(clippy::all)]
+#![warn(clippy::pedantic)]
+
+pub mod error;
+pub mod filters;
+pub mod query;
+pub mod todo;
+
+pub use error::Error;
+pub use filters::Filters;
+pub use query::Query;
+pub use todo::{ListHandle, ListRep, ListSnapshot, ParsedLine};
@@ -0,0 +1,111 @@
+use crate::{
+    app::{BlockState, View, ViewKind},
+    event::{Event, Handler as EventHandler},
+    logger,
+    runner::Action,
+};
+
+use log::Level;
+use termion::event::Key;
+use tui::{
+    backend::Backend,
+    layout::{
+        Constraint::{Length, Min},
+        Direction, Layout,
+    },
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Frame,
+};
+
+/// `LogView` shows the latest lines logged, newest at the bottom
+pub struct LogView<'a> {
+    state: &'a mut BlockState,
+    status: &'a mut Option<String>,
+    lines: Vec<(Level, String)>,
+}
+
+impl<'a> LogView<'a> {
+    pub fn new(state: &'a mut BlockState, status: &'a mut Option<String>) -> Self {
+        let lines = logger::recent();
+        // the last line stays selected as more are logged
+        let last = lines.len().checked_sub(1);
+        let following = state.pos.selected().is_none()
+            || state.pos.selected().map(|s| s + 1) == Some(state.len());
+        state.reset(lines.len());
+        if following {
+            state.pos.select(last);
+        }
+        Self {
+            state,
+            status,
+            lines,
+        }
+    }
+}
+
+fn level_style(level: Level) -> Style {
+    match level {
+        Level::Error => Style::default().fg(Color::Red),
+        Level::Warn => Style::default().fg(Color::Yellow),
+        Level::Info => Style::default(),
+        Level::Debug | Level::Trace => Style::default().fg(Color::DarkGray),
+    }
+}
+
+impl<'a> View for LogView<'a> {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Min(0), Length(1)].as_ref())
+            .split(f.size());
+
+        let title = format!(
+            "Log ({}, {})",
+            logger::level_filter().to_string().to_lowercase(),
+            logger::path().display()
+        );
+        let items: Vec<ListItem> = self
+            .lines
+            .iter()
+            .map(|(level, line)| ListItem::new(Span::styled(&line[..], level_style(*level))))
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, chunks[0], &mut self.state.pos);
+
+        let status = match (&self.status, self.lines.is_empty()) {
+            (Some(s), _) => Span::styled(&s[..], Style::default().fg(Color::Yellow)),
+            (None, true) => Span::styled("nothing logged", Style::default().fg(Color::DarkGray)),
+            (None, false) => Span::raw(""),
+        };
+        f.render_widget(Paragraph::new(Spans::from(status)), chunks[1]);
+    }
+}
+
+impl<'a> EventHandler<Key> for LogView<'a> {
+    fn handle(&mut self, event: Event<Key>) -> Option<Action> {
+        let Event::Input(key) = event else {
+            // picks up whatever was logged since
+            return Some(Action::Refresh);
+        };
+
+        *self.status = None;
+        match key {
+            Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => return Some(Action::Exit),
+            Key::Char('j') | Key::Down => self.state.next(),
+            Key::Char('k') | Key::Up => self.state.previous(),
+            Key::Char('g') | Key::Home => self.state.pos.select(Some(0)),
+            Key::Char('G') | Key::End => {
+                self.state.pos.select(self.lines.len().checked_sub(1));
+            }
+            Key::Char('r') => return Some(Action::Reload),
+            Key::Char('L') | Key::Esc => return Some(Action::SwitchView(ViewKind::Main)),
+            _ => {}
+        }
+        None
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{config, error::Error};
+
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    collections::VecDeque,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+/// the environment variable setting the level when `--verbose` isn't given,
+/// one of `off`, `error`, `warn`, `info`, `debug` or `trace`
+pub const LEVEL_VAR: &str = "TODOTUI_LOG";
+
+const LOG_FILE: &str = "todotui-rs.log";
+/// how large the log may grow before it's moved aside for a new one
+const MAX_SIZE: u64 = 1024 * 1024;
+/// how many of the old logs are kept, as `todotui-rs.log.1` and up
+const KEEP: usize = 3;
+/// how many lines are kept for the log view
+const RECENT: usize = 500;
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// `Logger` writes each record to the log file in the state directory and
+/// keeps the latest of them for the log view
+struct Logger {
+    level: LevelFilter,
+    path: PathBuf,
+    file: Mutex<Option<Sink>>,
+    recent: Mutex<VecDeque<(Level, String)>>,
+}
+
+/// `Sink` is the open log file and how much has been written to it
+struct Sink {
+    file: File,
+    size: u64,
+}
+
+impl Logger {
+    /// `open` opens the log file for appending, moving it aside first if
+    /// it's grown too large
+    fn open(&self) -> Result<Sink, Error> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(Error::io(dir))?;
+        }
+        let mut size = fs::metadata(&self.path).map_or(0, |m| m.len());
+        if size >= MAX_SIZE {
+            self.rotate().map_err(Error::io(&self.path))?;
+            size = 0;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(Error::io(&self.path))?;
+        Ok(Sink { file, size })
+    }
+
+    /// `rotate` shifts each old log up by one, dropping the oldest, failing
+    /// only if the current log can't be moved aside
+    fn rotate(&self) -> io::Result<()> {
+        let numbered = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        for n in (1..KEEP).rev() {
+            // the older logs needn't all be there
+            let _ = fs::rename(numbered(n), numbered(n + 1));
+        }
+        fs::rename(&self.path, numbered(1))
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = format!(
+            "{} {:<5} {}: {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        if let Ok(mut sink) = self.file.lock() {
+            if sink.as_ref().is_some_and(|s| s.size >= MAX_SIZE) {
+                // opening it again moves it aside, or gives up on the file
+                // if it can't be
+                *sink = None;
+                *sink = self.open().ok();
+            }
+            if let Some(sink) = sink.as_mut() {
+                // a log which can't be written shouldn't take the program down
+                if writeln!(sink.file, "{}", line).is_ok() {
+                    sink.size += line.len() as u64 + 1;
+                }
+            }
+        }
+
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == RECENT {
+                recent.pop_front();
+            }
+            recent.push_back((record.level(), line));
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut sink) = self.file.lock() {
+            if let Some(sink) = sink.as_mut() {
+                let _ = sink.file.flush();
+            }
+        }
+    }
+}
+
+/// `level` is `info`, raised by each `--verbose` given, otherwise whatever
+/// `$TODOTUI_LOG` says
+fn level(verbosity: u64) -> Result<LevelFilter, Error> {
+    match (verbosity, env::var(LEVEL_VAR)) {
+        (0, Ok(name)) => name
+            .parse()
+            .map_err(|_| Error::Usage(format!("{}: unknown log level {}", LEVEL_VAR, name))),
+        (0, Err(_)) => Ok(LevelFilter::Info),
+        (1, _) => Ok(LevelFilter::Debug),
+        _ => Ok(LevelFilter::Trace),
+    }
+}
+
+/// `init` starts logging to `$XDG_STATE_HOME/todotui-rs/todotui-rs.log`. If
+/// the file can't be opened the error is returned, but the latest lines are
+/// still kept for the log view.
+pub fn init(verbosity: u64) -> Result<(), Error> {
+    let level = level(verbosity)?;
+    let logger = LOGGER.get_or_init(|| Logger {
+        level,
+        path: path(),
+        file: Mutex::new(None),
+        recent: Mutex::new(VecDeque::with_capacity(RECENT)),
+    });
+    log::set_logger(logger).map_err(|e| Error::Usage(e.to_string()))?;
+    log::set_max_level(level);
+
+    let sink = logger.open()?;
+    if let Ok(mut file) = logger.file.lock() {
+        *file = Some(sink);
+    }
+    Ok(())
+}
+
+/// `path` is where the log is written
+pub fn path() -> PathBuf {
+    config::state_dir().join(LOG_FILE)
+}
+
+/// `level_filter` is the level records are logged at, `off` before `init`
+pub fn level_filter() -> LevelFilter {
+    LOGGER.get().map_or(LevelFilter::Off, |l| l.level)
+}
+
+/// `recent` are the latest lines logged, oldest first
+pub fn recent() -> Vec<(Level, String)> {
+    LOGGER
+        .get()
+        .and_then(|l| l.recent.lock().ok())
+        .map(|recent| recent.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    /// `logger` logs at `level` to a log in a directory of its own
+    fn logger(name: &str, level: LevelFilter) -> Logger {
+        let dir = std::env::temp_dir().join(format!("todotui-rs-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Logger {
+            level,
+            path: dir.join(LOG_FILE),
+            file: Mutex::new(None),
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn log(logger: &Logger, level: Level, message: &str) {
+        logger.log(
+            &Record::builder()
+                .level(level)
+                .target("test")
+                .args(format_args!("{}", message))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn logs_at_the_level_and_above() {
+        let logger = logger("log-level", LevelFilter::Info);
+        *logger.file.lock().unwrap() = Some(logger.open().unwrap());
+        log(&logger, Level::Debug, "hidden");
+        log(&logger, Level::Warn, "shown");
+        logger.flush();
+
+        let recent = logger.recent.lock().unwrap();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].0, Level::Warn);
+        assert!(recent[0].1.ends_with("WARN  test: shown"));
+        let written = fs::read_to_string(&logger.path).unwrap();
+        assert_eq!(written.lines().collect::<Vec<_>>(), [&recent[0].1[..]]);
+        fs::remove_dir_all(logger.path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn moves_a_full_log_aside() {
+        let logger = logger("log-rotate", LevelFilter::Info);
+        let numbered = |n: usize| PathBuf::from(format!("{}.{}", logger.path.display(), n));
+        for n in 1..=KEEP {
+            fs::write(numbered(n), n.to_string()).unwrap();
+        }
+        File::create(&logger.path)
+            .and_then(|f| f.set_len(MAX_SIZE))
+            .unwrap();
+
+        let sink = logger.open().unwrap();
+        assert_eq!(sink.size, 0);
+        assert_eq!(fs::metadata(&logger.path).unwrap().len(), 0);
+        assert_eq!(fs::metadata(numbered(1)).unwrap().len(), MAX_SIZE);
+        assert_eq!(fs::read_to_string(numbered(2)).unwrap(), "1");
+        assert_eq!(fs::read_to_string(numbered(3)).unwrap(), "2");
+        assert!(!numbered(KEEP + 1).exists());
+        fs::remove_dir_all(logger.path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn verbose_raises_the_level() {
+        assert_eq!(level(1).unwrap(), LevelFilter::Debug);
+        assert_eq!(level(2).unwrap(), LevelFilter::Trace);
+    }
+}
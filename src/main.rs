@@ -5,22 +5,63 @@ mod runner;
 mod event;
 mod flags;
 mod app;
-mod todo;
-mod filters;
-
-use std::{error::Error, fs::File};
-use std::io::prelude::*;
-
-fn main() -> Result<(), Box<dyn Error>> {
-    flags::parse();
-    match runner::start_term() {
-        Ok(()) => Ok(()),
-        Err(e) => {
-            // TODO: only doing this because errors dont print to the console
-            // find a better way
-            let mut f = File::create("/tmp/todotui-rs.log")?;
-            writeln!(f, "fatal: {}", e)?;
-            Err(e)
-        }
+mod config;
+mod calendar;
+mod agenda;
+mod board;
+mod timer;
+mod pomodoro;
+mod stats;
+mod projects;
+mod sidebar;
+mod cli;
+mod addons;
+mod hooks;
+mod scripts;
+mod logger;
+mod log_view;
+mod backups;
+mod recovery;
+
+use clap::ArgMatches;
+use std::process;
+use todotui_rs::{error, filters, query, todo};
+use error::Error;
+use todo::date::{self, SystemClock};
+
+fn main() {
+    let matches = flags::parse();
+    if let Err(e) = logger::init(matches.occurrences_of("verbose")) {
+        eprintln!("not logging: {}", e);
+    }
+
+    // the interface has put the terminal back by the time an error gets here
+    if let Err(e) = run(&matches) {
+        log::error!("{}", e);
+        eprintln!("{}", e);
+        process::exit(1);
     }
 }
+
+fn run(matches: &ArgMatches) -> Result<(), Error> {
+    if let Some(mut range) = matches.values_of("report") {
+        let mut next_date = || {
+            let expr = range.next().unwrap_or_default();
+            date::parse(expr, &SystemClock).ok_or_else(|| format!("unrecognised date: {}", expr))
+        };
+        let (from, to) = (next_date()?, next_date()?);
+        println!("{}", timer::report(from, to)?);
+        return Ok(());
+    }
+
+    if let (name, Some(args)) = matches.subcommand() {
+        return cli::run(name, args);
+    }
+
+    let view = matches
+        .value_of("view")
+        .and_then(app::ViewKind::from_name)
+        .unwrap_or(app::ViewKind::Main);
+
+    runner::start_term(view)
+}
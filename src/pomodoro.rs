@@ -0,0 +1,285 @@
+use crate::{
+    app::{View, ViewKind},
+    config::PomodoroConfig,
+    event::{Event, Handler as EventHandler},
+    runner::Action,
+    todo::date::Clock,
+};
+
+use chrono::{Duration, NaiveDateTime};
+use std::{
+    convert::TryFrom,
+    io::{self, Write},
+    process::{Command, Stdio},
+    thread,
+};
+use termion::event::Key;
+use tui::{
+    backend::Backend,
+    layout::{
+        Constraint::{Length, Min},
+        Direction, Layout,
+    },
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Gauge, Paragraph},
+    Frame,
+};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Phase {
+    Work,
+    Break,
+    LongBreak,
+}
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Work => "work",
+            Phase::Break => "break",
+            Phase::LongBreak => "long break",
+        }
+    }
+
+    fn minutes(self, config: &PomodoroConfig) -> i64 {
+        match self {
+            Phase::Work => config.work,
+            Phase::Break => config.short_break,
+            Phase::LongBreak => config.long_break,
+        }
+    }
+}
+
+/// Session is a run of pomodoros on one task. It's kept in the app state so
+/// it carries on when the view is rebuilt after the task line changes.
+pub struct Session {
+    /// the `id:` of the task, which stays with it as lines move
+    pub task: String,
+    pub body: String,
+    phase: Phase,
+    started: NaiveDateTime,
+    /// the time left in the phase while paused
+    paused: Option<Duration>,
+    completed: u32,
+}
+
+impl Session {
+    pub fn new(task: String, body: String, now: NaiveDateTime) -> Self {
+        Self {
+            task,
+            body,
+            phase: Phase::Work,
+            started: now,
+            paused: None,
+            completed: 0,
+        }
+    }
+
+    fn length(&self, config: &PomodoroConfig) -> Duration {
+        Duration::minutes(self.phase.minutes(config))
+    }
+
+    fn remaining(&self, config: &PomodoroConfig, now: NaiveDateTime) -> Duration {
+        self.paused
+            .unwrap_or_else(|| self.length(config) - (now - self.started))
+    }
+
+    fn toggle_pause(&mut self, config: &PomodoroConfig, now: NaiveDateTime) {
+        match self.paused.take() {
+            // the phase is restarted so that what's left of it runs from now
+            Some(left) => self.started = now - (self.length(config) - left),
+            None => self.paused = Some(self.remaining(config, now)),
+        }
+    }
+
+    /// `advance` moves on to the next phase, returning whether a work interval
+    /// was just completed. A skipped work interval doesn't count.
+    fn advance(&mut self, config: &PomodoroConfig, now: NaiveDateTime, finished: bool) -> bool {
+        let completed = finished && self.phase == Phase::Work;
+        if completed {
+            self.completed += 1;
+        }
+        self.phase = match self.phase {
+            Phase::Work if completed && self.completed.is_multiple_of(config.long_every) => {
+                Phase::LongBreak
+            }
+            Phase::Work => Phase::Break,
+            Phase::Break | Phase::LongBreak => Phase::Work,
+        };
+        self.started = now;
+        self.paused = None;
+        completed
+    }
+}
+
+pub struct PomodoroView<'a> {
+    session: &'a mut Option<Session>,
+    status: &'a mut Option<String>,
+    config: &'a PomodoroConfig,
+    clock: &'a dyn Clock,
+}
+
+impl<'a> PomodoroView<'a> {
+    pub fn new(
+        session: &'a mut Option<Session>,
+        status: &'a mut Option<String>,
+        config: &'a PomodoroConfig,
+        clock: &'a dyn Clock,
+    ) -> Self {
+        Self {
+            session,
+            status,
+            config,
+            clock,
+        }
+    }
+
+    /// `signal` marks the start of `phase` by running the configured command,
+    /// or otherwise by ringing the terminal bell
+    fn signal(&mut self, phase: Phase) {
+        let res = if let Some(command) = &self.config.command {
+            Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .env("POMODORO_PHASE", phase.name())
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map(|mut child| {
+                    // waited on out of the way, so it isn't left a zombie
+                    thread::spawn(move || child.wait());
+                })
+        } else {
+            let mut stdout = io::stdout();
+            stdout.write_all(b"\x07").and_then(|()| stdout.flush())
+        };
+
+        *self.status = Some(match res {
+            Ok(()) => format!("{} started", phase.name()),
+            Err(e) => format!("pomodoro signal failed: {}", e),
+        });
+    }
+
+    fn advance(&mut self, finished: bool) -> Option<Action> {
+        let now = self.clock.now();
+        let session = self.session.as_mut()?;
+        let completed = session.advance(self.config, now, finished);
+        let (phase, task) = (session.phase, session.task.clone());
+
+        self.signal(phase);
+        if completed {
+            Some(Action::LogPomodoro(task))
+        } else {
+            None
+        }
+    }
+}
+
+/// `clock_face` writes a duration as minutes and seconds
+fn clock_face(d: Duration) -> String {
+    let secs = d.num_seconds().max(0);
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+impl<'a> View for PomodoroView<'a> {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Length(4), Length(3), Min(0), Length(1)].as_ref())
+            .split(f.size());
+
+        let status = self.status.as_ref().map_or_else(Spans::default, |s| {
+            Spans::from(Span::styled(&s[..], Style::default().fg(Color::Yellow)))
+        });
+        f.render_widget(Paragraph::new(status), chunks[3]);
+
+        let Some(session) = self.session.as_ref() else {
+            let help = Paragraph::new("pick a task in the main view and press p to start")
+                .block(Block::default().borders(Borders::ALL).title("Pomodoro"));
+            f.render_widget(help, chunks[0]);
+            return;
+        };
+
+        let now = self.clock.now();
+        let length = session.length(self.config).num_seconds().max(1);
+        let left = session.remaining(self.config, now);
+        let percent = (length - left.num_seconds().max(0)) * 100 / length;
+
+        let info = Paragraph::new(vec![
+            Spans::from(Span::styled(
+                &session.body[..],
+                Style::default().add_modifier(Modifier::BOLD),
+            )),
+            Spans::from(Span::styled(
+                format!(
+                    "{} pomodoro(s) this session  space: pause  n: skip  esc: stop",
+                    session.completed
+                ),
+                Style::default().fg(Color::DarkGray),
+            )),
+        ])
+        .block(Block::default().borders(Borders::ALL).title("Pomodoro"));
+        f.render_widget(info, chunks[0]);
+
+        let color = if session.phase == Phase::Work {
+            Color::Red
+        } else {
+            Color::Green
+        };
+        let label = if session.paused.is_some() {
+            format!("{} paused", clock_face(left))
+        } else {
+            format!("{} left", clock_face(left))
+        };
+        let gauge = Gauge::default()
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(session.phase.name()),
+            )
+            .gauge_style(Style::default().fg(color))
+            .percent(u16::try_from(percent.clamp(0, 100)).unwrap_or(100))
+            .label(label);
+        f.render_widget(gauge, chunks[1]);
+    }
+}
+
+impl<'a> EventHandler<Key> for PomodoroView<'a> {
+    fn handle(&mut self, event: Event<Key>) -> Option<Action> {
+        match event {
+            Event::Input(key) => {
+                *self.status = None;
+                match key {
+                    Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => Some(Action::Exit),
+                    Key::Esc => {
+                        *self.session = None;
+                        Some(Action::SwitchView(ViewKind::Main))
+                    }
+                    Key::Char(' ') => {
+                        let now = self.clock.now();
+                        if let Some(session) = self.session.as_mut() {
+                            session.toggle_pause(self.config, now);
+                        }
+                        None
+                    }
+                    Key::Char('n') => self.advance(false),
+                    _ => None,
+                }
+            }
+            Event::Tick => {
+                let now = self.clock.now();
+                let session = self.session.as_ref()?;
+                if session.paused.is_none()
+                    && session.remaining(self.config, now) <= Duration::zero()
+                {
+                    self.advance(true)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{
+    app::{BlockState, View, ViewKind},
+    event::{Event, Handler as EventHandler},
+    runner::Action,
+    todo::{date, ParsedLine},
+};
+
+use chrono::NaiveDate;
+use std::convert::TryFrom;
+use termion::event::Key;
+use tui::{
+    backend::Backend,
+    layout::{
+        Constraint::{Length, Min},
+        Direction, Layout, Rect,
+    },
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, Gauge, Paragraph},
+    Frame,
+};
+
+/// `Summary` is the progress of one project across the todo and done files
+struct Summary {
+    name: String,
+    open: usize,
+    done: usize,
+    next_due: Option<NaiveDate>,
+    /// the latest day a task in the project was created or completed
+    last_activity: Option<NaiveDate>,
+}
+
+impl Summary {
+    fn percent(&self) -> u16 {
+        let total = self.open + self.done;
+        if total == 0 {
+            return 0;
+        }
+        u16::try_from(self.done * 100 / total).unwrap_or(100)
+    }
+}
+
+fn summarise(projects: &[String], tasks: &[String], done: &[String]) -> Vec<Summary> {
+    let mut summaries: Vec<Summary> = projects
+        .iter()
+        .map(|name| Summary {
+            name: name.clone(),
+            open: 0,
+            done: 0,
+            next_due: None,
+            last_activity: None,
+        })
+        .collect();
+
+    for (i, line) in tasks.iter().chain(done).enumerate() {
+        let item = ParsedLine::new(line, i);
+        let due = item.due_date.and_then(date::from_iso);
+        let active = item
+            .completion_date
+            .into_iter()
+            .chain(item.start_date)
+            .filter_map(date::from_iso)
+            .max();
+
+        for summary in &mut summaries {
+            if !item.tags.contains(&&summary.name[..]) {
+                continue;
+            }
+            if item.complete {
+                summary.done += 1;
+            } else {
+                summary.open += 1;
+                if let Some(due) = due {
+                    summary.next_due = Some(summary.next_due.map_or(due, |d| d.min(due)));
+                }
+            }
+            summary.last_activity = summary.last_activity.max(active);
+        }
+    }
+
+    summaries
+}
+
+pub struct ProjectsView<'a> {
+    state: &'a mut BlockState,
+    status: &'a mut Option<String>,
+    summaries: Vec<Summary>,
+    today: NaiveDate,
+}
+
+impl<'a> ProjectsView<'a> {
+    pub fn new(
+        state: &'a mut BlockState,
+        status: &'a mut Option<String>,
+        projects: &[String],
+        (tasks, done): (&[String], &[String]),
+        today: NaiveDate,
+    ) -> Self {
+        let summaries = summarise(projects, tasks, done);
+        state.reset(summaries.len());
+
+        Self {
+            state,
+            status,
+            summaries,
+            today,
+        }
+    }
+
+    fn describe_date(&self, label: &str, d: Option<NaiveDate>) -> String {
+        match d {
+            Some(d) if d == self.today => format!("{} today", label),
+            Some(d) if d < self.today => {
+                format!("{} {} ago", label, date::span((self.today - d).num_days()))
+            }
+            Some(d) => format!("{} in {}", label, date::span((d - self.today).num_days())),
+            None => String::new(),
+        }
+    }
+
+    fn draw_row<B: Backend>(
+        &self,
+        f: &mut Frame<'_, B>,
+        summary: &Summary,
+        selected: bool,
+        row: Rect,
+    ) {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Length(24), Length(16), Min(10), Length(18), Length(22)].as_ref())
+            .split(row);
+
+        let name_style = if selected {
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let dim = Style::default().fg(Color::DarkGray);
+        let overdue = summary.next_due.is_some_and(|d| d < self.today);
+
+        f.render_widget(
+            Paragraph::new(Span::styled(
+                format!("{}{}", if selected { "*" } else { " " }, summary.name),
+                name_style,
+            )),
+            columns[0],
+        );
+        f.render_widget(
+            Paragraph::new(Span::styled(
+                format!("{} open {} done", summary.open, summary.done),
+                dim,
+            )),
+            columns[1],
+        );
+        f.render_widget(
+            Gauge::default()
+                .gauge_style(Style::default().fg(Color::Green).bg(Color::Black))
+                .percent(summary.percent()),
+            columns[2],
+        );
+        f.render_widget(
+            Paragraph::new(Span::styled(
+                format!(" {}", self.describe_date("due", summary.next_due)),
+                if overdue {
+                    Style::default().fg(Color::Red)
+                } else {
+                    dim
+                },
+            )),
+            columns[3],
+        );
+        f.render_widget(
+            Paragraph::new(Span::styled(
+                self.describe_date("active", summary.last_activity),
+                dim,
+            )),
+            columns[4],
+        );
+    }
+}
+
+impl<'a> View for ProjectsView<'a> {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Min(0), Length(1)].as_ref())
+            .split(f.size());
+
+        let block = Block::default().borders(Borders::ALL).title("Projects");
+        let inner = block.inner(chunks[0]);
+        f.render_widget(block, chunks[0]);
+
+        // every project takes a line, scrolled to keep the selection in view
+        let height = usize::from(inner.height).max(1);
+        let selected = self.state.pos.selected();
+        let offset = selected.map_or(0, |s| (s + 1).saturating_sub(height));
+        for (n, summary) in self.summaries.iter().enumerate().skip(offset).take(height) {
+            let y = u16::try_from(n - offset).unwrap_or(0);
+            let row = Rect::new(inner.x, inner.y + y, inner.width, 1);
+            self.draw_row(f, summary, selected == Some(n), row);
+        }
+
+        if self.summaries.is_empty() {
+            f.render_widget(
+                Paragraph::new(Span::styled(
+                    "no projects",
+                    Style::default().fg(Color::DarkGray),
+                )),
+                inner,
+            );
+        }
+
+        let status = self.status.as_ref().map_or_else(Spans::default, |s| {
+            Spans::from(Span::styled(&s[..], Style::default().fg(Color::Yellow)))
+        });
+        f.render_widget(Paragraph::new(status), chunks[1]);
+    }
+}
+
+impl<'a> EventHandler<Key> for ProjectsView<'a> {
+    fn handle(&mut self, event: Event<Key>) -> Option<Action> {
+        let Event::Input(key) = event else {
+            return None;
+        };
+
+        *self.status = None;
+        match key {
+            Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => return Some(Action::Exit),
+            Key::Char('j') | Key::Down => self.state.next(),
+            Key::Char('k') | Key::Up => self.state.previous(),
+            Key::Char('\n') => return self.state.pos.selected().map(Action::FocusProject),
+            Key::Char('r') => return Some(Action::Reload),
+            Key::Char('P') | Key::Esc => return Some(Action::SwitchView(ViewKind::Main)),
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|&l| l.to_owned()).collect()
+    }
+
+    #[test]
+    fn summarises_across_both_files() {
+        let projects = lines(&["+home", "+work"]);
+        let tasks = lines(&[
+            "2020-10-10 call bob +work due:2020-10-20",
+            "write report +work due:2020-10-16",
+            "x 2020-10-12 book train +work",
+        ]);
+        let done = lines(&["x 2020-10-13 2020-10-01 file taxes +work +home"]);
+        let summaries = summarise(&projects, &tasks, &done);
+
+        let home = &summaries[0];
+        assert_eq!((home.open, home.done, home.percent()), (0, 1, 100));
+        assert_eq!(home.next_due, None);
+
+        let work = &summaries[1];
+        assert_eq!((work.open, work.done, work.percent()), (2, 2, 50));
+        assert_eq!(work.next_due, date::from_iso("2020-10-16"));
+        assert_eq!(work.last_activity, date::from_iso("2020-10-13"));
+    }
+
+    #[test]
+    fn nothing_done_of_nothing() {
+        let summaries = summarise(&lines(&["+idle"]), &[], &[]);
+        assert_eq!(summaries[0].percent(), 0);
+        assert_eq!(summaries[0].last_activity, None);
+    }
+}
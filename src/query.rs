@@ -0,0 +1,63 @@
+/// `Query` is a search over task lines in the style of `todo.sh list`. A
+/// line matches when it contains every term and none of the terms starting
+/// with `-`, ignoring case.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl Query {
+    pub fn new<'a, T>(terms: T) -> Self
+    where
+        T: IntoIterator<Item = &'a str>,
+    {
+        let mut query = Self::default();
+        for term in terms {
+            let term = term.to_lowercase();
+            match term.strip_prefix('-') {
+                Some(excluded) if !excluded.is_empty() => query.exclude.push(excluded.to_owned()),
+                _ => query.include.push(term),
+            }
+        }
+        query
+    }
+
+    #[must_use]
+    pub fn matches(&self, line: &str) -> bool {
+        let line = line.to_lowercase();
+        self.include.iter().all(|term| line.contains(&term[..]))
+            && !self.exclude.iter().any(|term| line.contains(&term[..]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_every_term_ignoring_case() {
+        let query = Query::new(vec!["BOB", "+home"]);
+        assert!(query.matches("call Bob +home"));
+        assert!(!query.matches("call bob +work"));
+    }
+
+    #[test]
+    fn excludes_terms_starting_with_a_dash() {
+        let query = Query::new(vec!["bob", "-@PHONE"]);
+        assert!(query.matches("visit bob @home"));
+        assert!(!query.matches("call bob @phone"));
+    }
+
+    #[test]
+    fn a_lone_dash_is_searched_for() {
+        let query = Query::new(vec!["-"]);
+        assert!(query.matches("a - b"));
+        assert!(!query.matches("a b"));
+    }
+
+    #[test]
+    fn no_terms_match_everything() {
+        assert!(Query::new(Vec::new()).matches("anything"));
+    }
+}
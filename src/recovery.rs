@@ -0,0 +1,406 @@
+use crate::{
+    config,
+    error::Error,
+    todo::{backup, ListHandle, ListRep},
+};
+
+use chrono::Local;
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    fs::{self, File, TryLockError},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// `Changes` are the lines of the todo file, and of the done file if it was
+/// changed too, which haven't been written yet
+#[derive(Debug, Clone, PartialEq)]
+pub struct Changes {
+    pub tasks: Vec<String>,
+    pub done: Option<Vec<String>>,
+}
+
+/// `Journal` is the recovery file of this instance, kept in the state
+/// directory much like a vim swap file. It's there for as long as the todo
+/// file is open, so other instances know it's being edited, and holds any
+/// changes not yet written, updated after each one. It's removed when the
+/// interface exits normally, so it's only found again after a crash.
+///
+/// The instance holds a lock on a file beside it while it runs, which is how
+/// others tell it's still running. Unlike its pid, the lock goes with it.
+pub struct Journal {
+    todo_path: PathBuf,
+    /// the directory holding the recovery files
+    dir: PathBuf,
+    path: PathBuf,
+    lock: Option<File>,
+    /// whether the file has been written, and the changes it holds, to skip
+    /// writing it again when nothing changed
+    written: bool,
+    recorded: Option<Changes>,
+    /// changes from a crashed instance to start from
+    recovered: Option<Changes>,
+    /// the recovery file they came from, kept until this one holds them
+    superseded: Option<PathBuf>,
+}
+
+/// `Opening` is how to go on after `check`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Opening {
+    ReadWrite,
+    ReadOnly,
+    Quit,
+}
+
+/// `Found` is the recovery file of another instance
+struct Found {
+    path: PathBuf,
+    pid: u32,
+    saved: String,
+    changes: Option<Changes>,
+}
+
+impl Journal {
+    pub fn new(todo_path: &Path) -> Self {
+        Self::in_dir(todo_path, config::state_dir().join("recovery"))
+    }
+
+    fn in_dir(todo_path: &Path, dir: PathBuf) -> Self {
+        let path = dir.join(format!("{}{}.json", prefix(todo_path), process::id()));
+        Self {
+            todo_path: todo_path.to_owned(),
+            dir,
+            path,
+            lock: None,
+            written: false,
+            recorded: None,
+            recovered: None,
+            superseded: None,
+        }
+    }
+
+    /// `record` writes whatever hasn't been written to the todo and done
+    /// files of `list_rep` to the recovery file
+    pub fn record(&mut self, list_rep: &ListRep) -> Result<(), Error> {
+        let changes = (list_rep.modified || list_rep.done_modified).then(|| Changes {
+            tasks: list_rep.tasks.clone(),
+            done: list_rep.done_modified.then(|| list_rep.done.clone()),
+        });
+        if self.written && self.recorded == changes {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir).map_err(Error::io(&self.dir))?;
+        if self.lock.is_none() {
+            // taken before the file is first written, so it's never found
+            // without the lock while this instance runs
+            let lock_path = lock_path(&self.path);
+            let lock = File::create(&lock_path).map_err(Error::io(&lock_path))?;
+            lock.try_lock()
+                .map_err(|e| Error::io(&lock_path)(e.into()))?;
+            self.lock = Some(lock);
+        }
+        let journal = json!({
+            "pid": process::id(),
+            "file": self.todo_path,
+            "saved": Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            "tasks": changes.as_ref().map(|c| &c.tasks),
+            "done": changes.as_ref().and_then(|c| c.done.as_ref()),
+        });
+        // written whole and moved into place, so it's never found half done
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, journal.to_string()).map_err(Error::io(&tmp_path))?;
+        fs::rename(&tmp_path, &self.path).map_err(Error::io(&self.path))?;
+        self.written = true;
+        self.recorded = changes;
+        if let Some(path) = self.superseded.take() {
+            remove(&path);
+        }
+        Ok(())
+    }
+
+    /// `clear` removes the recovery file, once there's nothing to recover
+    pub fn clear(&self) {
+        remove(&self.path);
+    }
+
+    /// `take_recovered` is the changes chosen to be recovered, if any
+    pub fn take_recovered(&mut self) -> Option<Changes> {
+        self.recovered.take()
+    }
+
+    /// `others` are the recovery files for the same todo file left by other
+    /// instances, running or not
+    fn others(&self) -> Vec<Found> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let prefix = prefix(&self.todo_path);
+        let mut found: Vec<Found> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| *path != self.path)
+            .filter(|path| {
+                let name = path.file_name().and_then(|n| n.to_str());
+                name.is_some_and(|n| n.starts_with(&prefix[..]))
+                    && path.extension().is_some_and(|ext| ext == "json")
+            })
+            .filter_map(read)
+            .collect();
+        found.sort_by(|a, b| b.saved.cmp(&a.saved));
+        found
+    }
+}
+
+/// `lock_path` is the file locked by the instance which keeps the recovery
+/// file at `path`, which can't be locked itself as each write replaces it
+fn lock_path(path: &Path) -> PathBuf {
+    path.with_extension("lock")
+}
+
+/// `remove` removes the recovery file at `path` along with its lock file
+fn remove(path: &Path) {
+    for path in &[path.to_owned(), lock_path(path)] {
+        if let Err(e) = fs::remove_file(path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("{}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// `prefix` starts the name of every recovery file for `todo_path`, keyed
+/// like its backups
+fn prefix(todo_path: &Path) -> String {
+    format!("{}.", backup::key(todo_path))
+}
+
+fn read(path: PathBuf) -> Option<Found> {
+    let value: Value = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+    let lines = |key: &str| -> Option<Vec<String>> {
+        value
+            .get(key)?
+            .as_array()?
+            .iter()
+            .map(|line| line.as_str().map(str::to_owned))
+            .collect()
+    };
+
+    let changes = lines("tasks").map(|tasks| Changes {
+        tasks,
+        done: lines("done"),
+    });
+    Some(Found {
+        pid: value.get("pid")?.as_u64().and_then(|p| u32::try_from(p).ok())?,
+        saved: value.get("saved")?.as_str()?.to_owned(),
+        changes,
+        path,
+    })
+}
+
+/// `running` is whether the instance which left the recovery file at `path`
+/// is still running, holding the lock beside it
+fn running(path: &Path) -> bool {
+    File::open(lock_path(path))
+        .is_ok_and(|lock| matches!(lock.try_lock(), Err(TryLockError::WouldBlock)))
+}
+
+/// `ask` reads one of `choices` from the terminal, or `q` at the end of input
+fn ask(question: &str, choices: &str) -> Result<char, Error> {
+    let stdin = io::stdin();
+    loop {
+        print!("{} ", question);
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer)? == 0 {
+            return Ok('q');
+        }
+        if let Some(choice) = answer.trim().chars().next().filter(|c| choices.contains(*c)) {
+            return Ok(choice);
+        }
+    }
+}
+
+/// `print_diff` shows the lines only in `file`, then those only in `changes`
+fn print_diff(path: &Path, file: &[String], changes: &[String]) {
+    let file_lines: HashSet<&String> = file.iter().collect();
+    let changed_lines: HashSet<&String> = changes.iter().collect();
+    println!("--- {}", path.display());
+    println!("+++ unsaved changes");
+    for line in file.iter().filter(|l| !changed_lines.contains(l)) {
+        println!("-{}", line);
+    }
+    for line in changes.iter().filter(|l| !file_lines.contains(l)) {
+        println!("+{}", line);
+    }
+}
+
+/// `check` looks for the recovery files of other instances before the
+/// interface starts. A running instance is warned about, and the changes
+/// left by one which crashed can be recovered, compared with the todo file
+/// or discarded.
+pub fn check(journal: &mut Journal) -> Result<Opening, Error> {
+    for found in journal.others() {
+        if running(&found.path) {
+            println!(
+                "{} is already being edited by todotui-rs (pid {}), since {}.",
+                journal.todo_path.display(),
+                found.pid,
+                found.saved
+            );
+            // it holds the file open, so this one couldn't write it anyway
+            match ask("open [r]ead only or [q]uit?", "rq")? {
+                'r' => return Ok(Opening::ReadOnly),
+                _ => return Ok(Opening::Quit),
+            }
+        }
+
+        let Some(changes) = found.changes else {
+            // it exited before it could clean up, with nothing unsaved
+            remove(&found.path);
+            continue;
+        };
+        println!(
+            "{} has changes from {} which weren't written, left by todotui-rs (pid {}).",
+            journal.todo_path.display(),
+            found.saved,
+            found.pid
+        );
+        loop {
+            match ask("[r]ecover, [d]iff, [x] discard or [q]uit?", "rdxq")? {
+                'r' => {
+                    info!("recovering the changes in {}", found.path.display());
+                    journal.recovered = Some(changes);
+                    // removed once this instance's recovery file has them,
+                    // so they aren't lost if it fails before then
+                    journal.superseded = Some(found.path);
+                    return Ok(Opening::ReadWrite);
+                }
+                'd' => {
+                    let handle = ListHandle::new(&journal.todo_path);
+                    let file = handle.get_lines().unwrap_or_default();
+                    print_diff(&journal.todo_path, &file, &changes.tasks);
+                    if changes.done.is_some() {
+                        println!("The done file was changed too.");
+                    }
+                }
+                'x' => {
+                    info!("discarding the changes in {}", found.path.display());
+                    remove(&found.path);
+                    break;
+                }
+                _ => return Ok(Opening::Quit),
+            }
+        }
+    }
+    Ok(Opening::ReadWrite)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `scratch` is an empty directory of its own for each test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("todotui-rs-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// `changed` is the todo file at `path` loaded with its lines changed
+    fn changed(path: &Path, tasks: &[&str]) -> ListRep {
+        fs::write(path, "call bob\n").unwrap();
+        let done = path.with_file_name("done.txt");
+        let mut list_rep = ListRep::new(&ListHandle::new(path), &ListHandle::new(&done)).unwrap();
+        list_rep.tasks = tasks.iter().map(|&t| t.to_owned()).collect();
+        list_rep.modified = true;
+        list_rep
+    }
+
+    #[test]
+    fn records_what_isnt_written() {
+        let dir = scratch("recovery-record");
+        let todo_path = dir.join("todo.txt");
+        let mut journal = Journal::in_dir(&todo_path, dir.join("recovery"));
+        journal
+            .record(&changed(&todo_path, &["call alice"]))
+            .unwrap();
+
+        let found = read(journal.path.clone()).unwrap();
+        assert_eq!(found.pid, process::id());
+        assert_eq!(
+            found.changes,
+            Some(Changes {
+                tasks: vec!["call alice".to_owned()],
+                done: None,
+            })
+        );
+        journal.clear();
+        assert!(!journal.path.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn running_while_the_lock_is_held() {
+        let dir = scratch("recovery-running");
+        let todo_path = dir.join("todo.txt");
+        let mut journal = Journal::in_dir(&todo_path, dir.join("recovery"));
+        journal
+            .record(&changed(&todo_path, &["call alice"]))
+            .unwrap();
+        assert!(running(&journal.path));
+
+        // as if it crashed, leaving both files behind
+        journal.lock = None;
+        assert!(!running(&journal.path));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn others_are_those_for_the_same_file() {
+        let dir = scratch("recovery-others");
+        let recovery = dir.join("recovery");
+        fs::create_dir_all(&recovery).unwrap();
+        let todo_path = dir.join("todo.txt");
+        fs::write(&todo_path, "").unwrap();
+        let journal = |pid: u32| json!({ "pid": pid, "saved": "2020-10-14 09:00:00" }).to_string();
+        let key = prefix(&todo_path);
+        fs::write(recovery.join(format!("{}1.json", key)), journal(1)).unwrap();
+        fs::write(recovery.join(format!("{}2.tmp", key)), journal(2)).unwrap();
+        fs::write(recovery.join("other.txt.3.json"), journal(3)).unwrap();
+
+        let pids: Vec<u32> = Journal::in_dir(&todo_path, recovery)
+            .others()
+            .iter()
+            .map(|f| f.pid)
+            .collect();
+        assert_eq!(pids, [1]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recovered_changes_are_kept_until_recorded_again() {
+        let dir = scratch("recovery-superseded");
+        let todo_path = dir.join("todo.txt");
+        let recovery = dir.join("recovery");
+        let mut crashed = Journal::in_dir(&todo_path, recovery.clone());
+        crashed.path = recovery.join(format!("{}1.json", prefix(&todo_path)));
+        crashed
+            .record(&changed(&todo_path, &["call alice"]))
+            .unwrap();
+        crashed.lock = None;
+
+        let mut journal = Journal::in_dir(&todo_path, recovery);
+        journal.superseded = Some(crashed.path.clone());
+        assert!(crashed.path.exists());
+        journal
+            .record(&changed(&todo_path, &["call alice"]))
+            .unwrap();
+        assert!(!crashed.path.exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
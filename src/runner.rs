@@ -1,15 +1,45 @@
-use crate::todo::{ListHandle, ListRep};
+use crate::todo::{
+    date::{self, Clock, SystemClock},
+    deps::Deps,
+    edit,
+    backup::Backup,
+    tree::{self, Tree},
+    ListHandle, ListRep,
+};
 use crate::{
-    app::{ActiveList, MainView, State},
+    addons,
+    agenda::AgendaView,
+    app::{ActiveList, MainView, State, View, ViewKind},
+    backups::{Backed, BackupsView},
+    board::BoardView,
+    calendar::CalendarView,
+    log_view::LogView,
+    recovery::{self, Changes, Journal, Opening},
+    pomodoro::{PomodoroView, Session},
+    projects::ProjectsView,
+    scripts::Scripts,
+    stats::StatsView,
     todo::ParsedLine,
 };
 use crate::{
-    event::{Generator, Handler},
+    config::{self, Config},
+    error::Error,
+    event::{Event, Generator},
     filters::Filters,
+    hooks::{self, Hook},
+    sidebar::make_filter_views,
+    timer::{self, Timer},
 };
 
-use chrono::Utc;
-use std::{collections::BTreeSet, error::Error, path::Path};
+use chrono::NaiveDate;
+use log::{debug, info, warn};
+use std::{
+    collections::BTreeSet,
+    io::{self, ErrorKind},
+    panic::{self, AssertUnwindSafe},
+    process,
+    sync::{Arc, Mutex},
+};
 use termion::{input::MouseTerminal, raw::IntoRawMode, screen::AlternateScreen};
 use tui::{
     backend::{Backend, TermionBackend},
@@ -18,134 +48,1058 @@ use tui::{
 
 #[derive(Debug)]
 pub enum Action {
-    Select(usize),
+    Select(usize, usize),
+    Complete(usize),
     Write,
+    /// replace the tasks with those in a backup of the todo file
+    RestoreBackup(Backup, Backed),
+    /// write even though the todo file was changed by something else
+    ForceWrite,
     Delete(usize),
     Reload,
     Exit,
+    Add(String),
+    AddChild(usize, String),
+    SetParent(usize, Option<usize>),
+    Edit(usize, String),
+    SetDue(usize, String),
+    Snooze(usize, String),
+    Defer(Vec<usize>, String),
+    ToggleTimer(usize),
+    StartPomodoro(usize),
+    LogPomodoro(String),
+    FocusProject(usize),
+    Retag(String, Option<String>),
+    Merge(String, String),
+    Undo,
+    RunAddon(String),
+    RunScript(Option<usize>, String),
+    SwitchView(ViewKind),
+    Refresh,
 }
 
-pub fn start_term() -> Result<(), Box<dyn Error>> {
-    let stdout = std::io::stdout().into_raw_mode()?;
-    let stdout = MouseTerminal::from(stdout);
-    let stdout = AlternateScreen::from(stdout);
-    let backend = TermionBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+/// `start_term` runs the interface in the alternate screen. The terminal is
+/// put back as it was before anything is printed about an error, which is
+/// handed back, or a panic, which ends the program.
+pub fn start_term(view: ViewKind) -> Result<(), Error> {
+    let config = Config::load()?;
+    let mut journal = Journal::new(&config::todo_dir().join(config::TODO_FILE));
+    let read_only = match recovery::check(&mut journal)? {
+        Opening::ReadWrite => None,
+        Opening::ReadOnly => Some("another instance is editing it"),
+        Opening::Quit => return Ok(()),
+    };
 
-    run_with_term(&mut terminal)
-}
+    // a panic message printed straight away would be lost with the alternate
+    // screen, so it's kept until the terminal has been dropped
+    let message = Arc::new(Mutex::new(None));
+    let hook_message = Arc::clone(&message);
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Ok(mut message) = hook_message.lock() {
+            *message = Some(info.to_string());
+        }
+    }));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let stdout = io::stdout().into_raw_mode()?;
+        let stdout = MouseTerminal::from(stdout);
+        let stdout = AlternateScreen::from(stdout);
+        let backend = TermionBackend::new(stdout);
+        let mut terminal = Terminal::new(backend)?;
 
-fn run_with_term<B: Backend>(terminal: &mut Terminal<B>) -> Result<(), Box<dyn Error>> {
-    const FILENAME: &str = "main.todo.txt";
+        run_with_term(&mut terminal, &config, view, &mut journal, read_only)
+    }));
+    panic::set_hook(default_hook);
 
+    // after a panic or an error the recovery file is left to be found
+    match result {
+        Ok(Ok(())) => {
+            journal.clear();
+            Ok(())
+        }
+        Ok(Err(e)) => Err(e),
+        Err(_) => {
+            let message = message.lock().ok().and_then(|mut m| m.take());
+            eprintln!("todotui-rs {}", message.unwrap_or_default());
+            process::exit(101);
+        }
+    }
+}
+
+fn run_with_term<B: Backend>(
+    terminal: &mut Terminal<B>,
+    config: &Config,
+    mut view: ViewKind,
+    journal: &mut Journal,
+    read_only: Option<&str>,
+) -> Result<(), Error> {
     let eventgen = Generator::new();
-    let todo_dir = Path::new(env!("TODO_DIR"));
-    let todo_path = todo_dir.join(FILENAME);
-    let list_handle = ListHandle::new(&todo_path);
+    let todo_dir = config::todo_dir();
+    let todo_path = todo_dir.join(config::TODO_FILE);
+    let done_path = todo_dir.join(config::DONE_FILE);
+    let backups = config.backups.backups();
+    let mut list_handle = ListHandle::new(&todo_path).backed_up(&backups);
+    let mut done_handle = ListHandle::new(&done_path).backed_up(&backups);
+    let clock = SystemClock;
 
+    // held until the interface exits, so another one opens the files read
+    // only, while writes are made under the lock `save` takes
+    let (read_only, _open) = match read_only {
+        Some(reason) => (Some(reason.to_owned()), None),
+        None => match list_handle.hold() {
+            Ok(lock) => (None, Some(lock)),
+            Err(Error::Locked { pid: Some(pid), .. }) => {
+                (Some(format!("process {} has it open", pid)), None)
+            }
+            Err(e) => (Some(format!("it couldn't be locked, {}", e)), None),
+        },
+    };
+    if let Some(reason) = read_only {
+        warn!("opening {} read only: {}", todo_path.display(), reason);
+        list_handle = list_handle.read_only(&reason);
+        done_handle = done_handle.read_only(&reason);
+    }
+
+    // the files are read afresh each time round, until the interface exits
     loop {
-        match run_with_file(terminal, &list_handle, &eventgen)? {
-            Action::Reload => {} // just continue
-            Action::Exit => break Ok(()),
-            action => panic!(format!("{:?} action unhandled at this stage", action)),
-        };
+        let action = run_with_file(
+            terminal,
+            (&list_handle, &done_handle),
+            &eventgen,
+            &clock,
+            config,
+            &mut view,
+            journal,
+        )?;
+        if let Action::Exit = action {
+            break Ok(());
+        }
+        info!("reloading {}", todo_path.display());
     }
 }
 
 fn run_with_file<B: Backend>(
     terminal: &mut Terminal<B>,
-    list_handle: &ListHandle,
+    (list_handle, done_handle): (&ListHandle, &ListHandle),
     eventgen: &Generator,
-) -> Result<Action, Box<dyn Error>> {
-    let mut list_rep = ListRep::new(list_handle)?;
+    clock: &dyn Clock,
+    config: &Config,
+    view: &mut ViewKind,
+    journal: &mut Journal,
+) -> Result<Action, Error> {
+    let mut list_rep = ListRep::new(list_handle, done_handle)?;
 
     let mut state = State::new(
         list_rep.tasks.len(),
         list_rep.filters.contexts.len(),
         list_rep.filters.tags.len(),
+        config.dates.relative,
+        clock.today(),
     );
+    state.view = *view;
+    state.sidebar = config.sidebar.clone();
+    match Timer::load() {
+        Ok(timer) => state.timer = timer,
+        Err(e) => state.status = Some(format!("timer: {}", e)),
+    }
+    match Scripts::load(clock.today()) {
+        Ok(scripts) => state.scripts = scripts,
+        Err(e) => state.status = Some(e),
+    }
+    notify(Hook::Reload, &list_rep.tasks, config, &mut state);
+    if let Some(changes) = journal.take_recovered() {
+        recover(changes, &mut list_rep, &mut state);
+    }
+    if list_handle.is_read_only() {
+        state.status = Some("opened read only, changes won't be written".to_owned());
+    }
 
     let mut active_filters: Filters<BTreeSet<&str>> =
         Filters::new(BTreeSet::new(), BTreeSet::new());
 
     loop {
-        let filtered_items: Vec<ParsedLine> = list_rep
-            .tasks
-            .iter()
-            .enumerate()
-            .filter_map(|(i, task)| {
-                if active_filters.include(task) {
-                    return Some(ParsedLine::new(&task[..], i));
+        if let Err(e) = journal.record(&list_rep) {
+            warn!("unsaved changes not recorded: {}", e);
+        }
+        let action = run_view(
+            terminal,
+            eventgen,
+            &mut state,
+            &list_rep,
+            &active_filters,
+            clock,
+            config,
+        )?;
+
+        match action {
+            Action::Select(i, len) => {
+                let source = &list_rep.filters;
+                toggle_filter(&mut active_filters, source, state.active_list, i, len);
+            }
+            Action::Write | Action::ForceWrite => {
+                let selected = selected_filters(&active_filters);
+                let force = matches!(action, Action::ForceWrite);
+                let files = (list_handle, done_handle);
+                write_files(files, &mut list_rep, &mut state, config, force);
+                active_filters = select_filters(&list_rep.filters, &selected);
+            }
+            Action::RunAddon(line) => {
+                let selected = selected_filters(&active_filters);
+                let files = (list_handle, done_handle);
+                run_addon(&line, files, &mut list_rep, &mut state, config);
+                active_filters = select_filters(&list_rep.filters, &selected);
+            }
+            Action::RunScript(selected, line) => {
+                let names = selected_filters(&active_filters);
+                run_script(selected, &line, &names, &mut list_rep, &mut state);
+                active_filters = select_filters(&list_rep.filters, &names);
+            }
+            action @ (Action::Retag(..)
+            | Action::Merge(..)
+            | Action::Undo
+            | Action::RestoreBackup(..)) => {
+                let mut selected = selected_filters(&active_filters);
+                apply_batch(action, &mut list_rep, &mut state, &mut selected);
+                active_filters = select_filters(&list_rep.filters, &selected);
+            }
+            Action::ToggleTimer(i) => {
+                let selected = selected_filters(&active_filters);
+                if let Err(e) = toggle_timer(i, list_handle, &mut list_rep, &mut state, clock) {
+                    state.status = Some(format!("timer: {}", e));
                 }
-                None
-            })
-            .collect();
+                active_filters = select_filters(&list_rep.filters, &selected);
+            }
+            Action::FocusProject(i) => {
+                // the project replaces any project filters already chosen
+                active_filters.tags.clear();
+                active_filters.tags.insert(&list_rep.filters.tags[i]);
+                state.view = ViewKind::Main;
+            }
+            Action::SwitchView(view) => state.view = view,
+            Action::Refresh => {}
+            action @ (Action::Reload | Action::Exit) => {
+                // the view is kept across reloads
+                *view = state.view;
+                return Ok(action);
+            }
+            action => {
+                if let Some(action) = apply_edit(
+                    action,
+                    &mut list_rep.tasks,
+                    &mut list_rep.modified,
+                    &mut state,
+                    config,
+                    clock,
+                ) {
+                    state.status = Some(format!("{:?} can't be done here", action));
+                }
+            }
+        }
+    }
+}
 
-        state.tasks.reset(filtered_items.len());
+/// `run_view` runs the current view over the tasks matching the active
+/// filters until it produces an action
+fn run_view<B: Backend>(
+    terminal: &mut Terminal<B>,
+    eventgen: &Generator,
+    state: &mut State,
+    list_rep: &ListRep,
+    active_filters: &Filters<BTreeSet<&str>>,
+    clock: &dyn Clock,
+    config: &Config,
+) -> Result<Action, Error> {
+    let deps = Deps::new(&parse_all(&list_rep.tasks), &parse_all(&list_rep.done));
+    report_warnings(&deps, state);
 
-        let main_view = MainView::new(
-            &mut state,
-            filtered_items,
-            Filters::new(
-                make_view_strings(&list_rep.filters.contexts, &active_filters.contexts),
-                make_view_strings(&list_rep.filters.tags, &active_filters.tags),
-            ),
-        );
+    let mut filtered_items = filter_items(&list_rep.tasks, active_filters, state, &deps);
+    let today = state.today;
+    state
+        .scripts
+        .arrange(&mut filtered_items, today, &mut state.status);
 
-        let action = run_with_view(terminal, eventgen, main_view)?;
+    let action = match state.view {
+        ViewKind::Main => {
+            let main_view = MainView::new(
+                state,
+                filtered_items,
+                make_filter_views(list_rep, active_filters, state),
+                list_rep,
+                &deps,
+                clock,
+                &config.dates,
+            );
 
-        match action {
-            Action::Select(i) => match state.active_list {
-                ActiveList::Tasks => {
-                    list_rep.tasks[i] = if list_rep.tasks[i].starts_with("x ") {
-                        list_rep.tasks[i]
-                            .splitn(3, ' ')
-                            .nth(2)
-                            .expect("what")
-                            .to_owned()
-                    } else {
-                        let dt = Utc::today();
-                        format!("x {} {}", dt.format("%Y-%m-%d"), list_rep.tasks[i])
-                    };
-                    list_rep.modified = true;
-                }
+            run_with_view(terminal, eventgen, main_view)?
+        }
+        ViewKind::Calendar => {
+            let calendar_view = CalendarView::new(
+                &mut state.calendar,
+                &mut state.status,
+                &mut state.today,
+                filtered_items,
+                state.timer.as_ref(),
+                clock,
+            );
 
-                al @ ActiveList::Contexts | al @ ActiveList::Tags => {
-                    let filters = active_filters.get_mut(al);
-                    let filter_source = list_rep.filters.get(al);
-                    if filters.contains(&filter_source[i][..]) {
-                        filters.remove(&filter_source[i][..]);
-                    } else {
-                        filters.insert(&filter_source[i]);
-                    }
-                }
-            },
-            Action::Write => {
-                if list_rep.modified {
-                    list_handle.write(&list_rep.tasks)?;
-                    list_rep.modified = false;
+            run_with_view(terminal, eventgen, calendar_view)?
+        }
+        ViewKind::Agenda => {
+            let agenda_view = AgendaView::new(
+                &mut state.agenda,
+                &mut state.status,
+                &mut state.today,
+                filtered_items,
+                state.timer.as_ref(),
+                clock,
+            );
+
+            run_with_view(terminal, eventgen, agenda_view)?
+        }
+        ViewKind::Board => {
+            let board_view = BoardView::new(
+                &mut state.board,
+                &mut state.status,
+                &config.board,
+                filtered_items,
+                state.timer.as_ref(),
+                clock,
+            );
+
+            run_with_view(terminal, eventgen, board_view)?
+        }
+        ViewKind::Pomodoro => {
+            let pomodoro_view = PomodoroView::new(
+                &mut state.pomodoro,
+                &mut state.status,
+                &config.pomodoro,
+                clock,
+            );
+
+            run_with_view(terminal, eventgen, pomodoro_view)?
+        }
+        ViewKind::Stats => {
+            let stats_view = StatsView::new(
+                &mut state.status,
+                &list_rep.tasks,
+                &list_rep.done,
+                state.today,
+            );
+
+            run_with_view(terminal, eventgen, stats_view)?
+        }
+        ViewKind::Projects => {
+            let projects_view = ProjectsView::new(
+                &mut state.projects,
+                &mut state.status,
+                &list_rep.filters.tags,
+                (&list_rep.tasks, &list_rep.done),
+                state.today,
+            );
+
+            run_with_view(terminal, eventgen, projects_view)?
+        }
+        ViewKind::Backups => {
+            let (name, lines) = match state.backups.file {
+                Backed::Todo => (config::TODO_FILE, &list_rep.tasks),
+                Backed::Done => (config::DONE_FILE, &list_rep.done),
+            };
+            let backups_view = BackupsView::new(
+                &mut state.backups,
+                &mut state.status,
+                config.backups.backups().list(&config::todo_dir().join(name)),
+                lines,
+            );
+
+            run_with_view(terminal, eventgen, backups_view)?
+        }
+        ViewKind::Log => {
+            let log_view = LogView::new(&mut state.log, &mut state.status);
+
+            run_with_view(terminal, eventgen, log_view)?
+        }
+    };
+
+    Ok(action)
+}
+
+/// `apply_edit` applies the actions which rewrite task lines, setting
+/// `modified` if any line changed. Any other action is handed back.
+///
+/// A change drops the batch snapshots, as undoing one would put back the
+/// whole file and so throw the change away.
+fn apply_edit(
+    action: Action,
+    tasks: &mut Vec<String>,
+    modified: &mut bool,
+    state: &mut State,
+    config: &Config,
+    clock: &dyn Clock,
+) -> Option<Action> {
+    let mut changed = false;
+    let action = edit_tasks(action, tasks, &mut changed, state, config, clock);
+    if changed {
+        *modified = true;
+        state.undo.clear();
+    }
+    action
+}
+
+/// `edit_tasks` rewrites the task lines for `apply_edit`, setting `modified`
+/// if any changed
+fn edit_tasks(
+    action: Action,
+    tasks: &mut Vec<String>,
+    modified: &mut bool,
+    state: &mut State,
+    config: &Config,
+    clock: &dyn Clock,
+) -> Option<Action> {
+    match action {
+        Action::Complete(i) => *modified |= toggle_complete(tasks, i, state, config, clock),
+        Action::Delete(i) => {
+            vet(Hook::Delete, vec![tasks[i].clone()], config, state)?;
+            tasks.remove(i);
+            *modified = true;
+            state.marked.clear();
+        }
+        Action::Add(line) => {
+            if !line.trim().is_empty() {
+                let line = ParsedLine::expand_dates(&line, clock);
+                tasks.extend(vet(Hook::Add, vec![line], config, state)?);
+                *modified = true;
+            }
+        }
+        Action::AddChild(parent, line) => {
+            if !line.trim().is_empty() {
+                let line = ParsedLine::expand_dates(&line, clock);
+                let line = vet(Hook::Add, vec![line], config, state)?.remove(0);
+                let id = ensure_id(tasks, parent);
+                tasks.push(edit::set_extension(&line, "parent", &id));
+                *modified = true;
+            }
+        }
+        Action::SetParent(i, parent) => {
+            let line = edit::remove_extension(&tasks[i], "p");
+            tasks[i] = match parent {
+                Some(p) => {
+                    let id = ensure_id(tasks, p);
+                    edit::set_extension(&line, "parent", &id)
                 }
+                None => edit::remove_extension(&line, "parent"),
+            };
+            *modified = true;
+        }
+        Action::Edit(i, line) => {
+            tasks[i] = ParsedLine::expand_dates(&line, clock);
+            *modified = true;
+        }
+        Action::SetDue(i, expr) => *modified |= set_date(&mut tasks[i], "due", &expr, clock, state),
+        Action::Snooze(i, expr) => *modified |= set_date(&mut tasks[i], "t", &expr, clock, state),
+        Action::Defer(indices, expr) => {
+            let count = indices.len();
+            for i in indices {
+                let line = if expr.trim().is_empty() {
+                    edit::remove_extension(&tasks[i], "t")
+                } else if let Some(line) =
+                    edit::defer(&tasks[i], &expr, config.defer.shift_due, clock)
+                {
+                    line
+                } else {
+                    state.status = Some(format!("unrecognised date: {}", expr));
+                    return None;
+                };
+                tasks[i] = line;
+                *modified = true;
             }
-            Action::Delete(i) => {
-                if ActiveList::Tasks == state.active_list {
-                    list_rep.tasks.remove(i);
-                    list_rep.modified = true;
+            state.status = Some(format!("deferred {} task(s)", count));
+            state.marked.clear();
+        }
+        Action::StartPomodoro(i) => {
+            let line = tasks[i].clone();
+            let id = ensure_id(tasks, i);
+            *modified |= tasks[i] != line;
+            let body = ParsedLine::new(&tasks[i], i).body;
+            state.pomodoro = Some(Session::new(id, body, clock.now()));
+            state.view = ViewKind::Pomodoro;
+        }
+        Action::LogPomodoro(id) => {
+            let Some(i) = tasks
+                .iter()
+                .position(|t| edit::get_extension(t, "id") == Some(&id[..]))
+            else {
+                state.status = Some(format!("pomodoro task id:{} is gone, not logged", id));
+                return None;
+            };
+            let count = edit::get_extension(&tasks[i], "pomo")
+                .and_then(|n| n.parse::<u32>().ok())
+                .unwrap_or(0);
+            tasks[i] = edit::set_extension(&tasks[i], "pomo", &(count + 1).to_string());
+            *modified = true;
+        }
+        action => return Some(action),
+    }
+    None
+}
+
+/// `toggle_complete` marks task `i` done or not done, returning whether it
+/// was. Completing takes the tasks nested under it along when configured and
+/// is run past the `on-complete` hook.
+fn toggle_complete(
+    tasks: &mut [String],
+    i: usize,
+    state: &mut State,
+    config: &Config,
+    clock: &dyn Clock,
+) -> bool {
+    let completing = !tasks[i].starts_with("x ");
+    let mut changed = if completing && config.tree.complete_children {
+        open_descendants(tasks, i)
+    } else {
+        Vec::new()
+    };
+    changed.push(i);
+
+    let lines = changed
+        .iter()
+        .map(|&c| edit::toggle_complete(&tasks[c], clock.today()))
+        .collect();
+    let lines = if completing {
+        match vet(Hook::Complete, lines, config, state) {
+            Some(lines) => lines,
+            None => return false,
+        }
+    } else {
+        lines
+    };
+    for (c, line) in changed.into_iter().zip(lines) {
+        tasks[c] = line;
+    }
+    true
+}
+
+/// `toggle_timer` stops the running timer, adding the time to its task's
+/// `spent:`, and unless it was timing task `i` starts a new one on it. The
+/// timer file names its task by `id:`, so one given to the task here is
+/// written to the todo file at once, in case the rest never is.
+fn toggle_timer(
+    i: usize,
+    list_handle: &ListHandle,
+    list_rep: &mut ListRep,
+    state: &mut State,
+    clock: &dyn Clock,
+) -> Result<(), Error> {
+    let now = clock.now();
+    let tasks = &mut list_rep.tasks;
+
+    if let Some(running) = &state.timer {
+        let minutes = (running.elapsed(now).num_seconds() + 30) / 60;
+        let found = tasks
+            .iter()
+            .position(|t| edit::get_extension(t, "id") == Some(&running.id[..]));
+
+        if let Some(t) = found {
+            let spent = edit::get_extension(&tasks[t], "spent")
+                .and_then(timer::parse_spent)
+                .unwrap_or(0);
+            let line =
+                edit::set_extension(&tasks[t], "spent", &timer::format_spent(spent + minutes));
+            running.log(now, &line)?;
+            tasks[t] = line;
+            list_rep.modified = true;
+            state.undo.clear();
+            state.status = Some(format!(
+                "spent {} on {}",
+                timer::format_spent(minutes),
+                running.body
+            ));
+        } else {
+            running.log(now, &running.body)?;
+            state.status = Some(format!(
+                "timed task id:{} is gone, time only logged",
+                running.id
+            ));
+        }
+
+        let stopped = edit::get_extension(&tasks[i], "id") == Some(&running.id[..]);
+        // the time is only forgotten once it's been logged
+        state.timer = None;
+        Timer::clear()?;
+        if stopped {
+            return Ok(());
+        }
+    }
+
+    let line = tasks[i].clone();
+    let id = ensure_id(tasks, i);
+    if tasks[i] != line {
+        let with_id = tasks[i].clone();
+        if let Err(e) = list_rep.write_line(list_handle, &line, &with_id) {
+            list_rep.tasks[i] = line;
+            return Err(e);
+        }
+    }
+
+    let body = ParsedLine::new(&list_rep.tasks[i], i).body;
+    let running = Timer::new(id, body, now);
+    running.save()?;
+    state.timer = Some(running);
+    Ok(())
+}
+
+/// `ensure_id` returns the `id:` of task `i`, first giving it the next free
+/// one if it has none
+fn ensure_id(tasks: &mut [String], i: usize) -> String {
+    if let Some(id) = edit::get_extension(&tasks[i], "id") {
+        return id.to_owned();
+    }
+    let id = tree::next_id(tasks).to_string();
+    tasks[i] = edit::set_extension(&tasks[i], "id", &id);
+    id
+}
+
+/// `open_descendants` are the incomplete tasks nested under task `i`
+fn open_descendants(tasks: &[String], i: usize) -> Vec<usize> {
+    let items = parse_all(tasks);
+    Tree::new(&items)
+        .descendants(i)
+        .into_iter()
+        .filter(|&d| !items[d].complete)
+        .collect()
+}
+
+/// `report_warnings` puts dependency warnings in the status bar as they
+/// change, rather than every time a view is built
+fn report_warnings(deps: &Deps, state: &mut State) {
+    if deps.warnings != state.warnings {
+        state.status = deps.summary();
+        state.warnings.clone_from(&deps.warnings);
+    }
+}
+
+/// `vet` runs a hook which can refuse or rewrite the lines of a change,
+/// handing back nothing with the reason in the status bar if it refused
+fn vet(hook: Hook, lines: Vec<String>, config: &Config, state: &mut State) -> Option<Vec<String>> {
+    match hooks::run(hook, &config.hooks, lines) {
+        Ok(lines) => Some(lines),
+        Err(e) => {
+            warn!("{}", e);
+            state.status = Some(e);
+            None
+        }
+    }
+}
+
+/// `notify` runs a hook which is only told about the lines, reporting it in
+/// the status bar if it fails
+fn notify(hook: Hook, lines: &[String], config: &Config, state: &mut State) {
+    if let Err(e) = hooks::run(hook, &config.hooks, lines.to_vec()) {
+        warn!("{}", e);
+        state.status = Some(e);
+    }
+}
+
+/// `write_files` writes out whichever of the todo and done files changed,
+/// returning whether they were. The `pre-write` hook may refuse the write or
+/// rewrite the todo file first, and `post-write` is told about the lines
+/// written. Unless `force` is set the todo file isn't written over changes
+/// made by something else.
+fn write_files(
+    (list_handle, done_handle): (&ListHandle, &ListHandle),
+    list_rep: &mut ListRep,
+    state: &mut State,
+    config: &Config,
+    force: bool,
+) -> bool {
+    if !list_rep.modified && !list_rep.done_modified {
+        return true;
+    }
+
+    let Some(tasks) = vet(Hook::PreWrite, list_rep.tasks.clone(), config, state) else {
+        return false;
+    };
+    if tasks != list_rep.tasks {
+        list_rep.tasks = tasks;
+        list_rep.modified = true;
+        list_rep.refresh_filters();
+        // the lines may have moved under the marks, and undoing a batch
+        // would throw the hook's changes away
+        state.marked.clear();
+        state.undo.clear();
+    }
+
+    if let Err(e) = list_rep.save(list_handle, done_handle, force) {
+        warn!("not written: {}", e);
+        state.status = Some(match e {
+            Error::Conflict { .. } => format!("{}, W to write anyway or r to reload", e),
+            e => format!("not written: {}", e),
+        });
+        return false;
+    }
+    notify(Hook::PostWrite, &list_rep.tasks, config, state);
+    true
+}
+
+/// `run_addon` runs a todo.sh add-on over the files, first writing out any
+/// changes so it sees them, then reads back whatever it changed
+fn run_addon(
+    line: &str,
+    files: (&ListHandle, &ListHandle),
+    list_rep: &mut ListRep,
+    state: &mut State,
+    config: &Config,
+) {
+    // the status bar already says why if it wasn't written
+    if !write_files(files, list_rep, state, config, false) {
+        return;
+    }
+
+    state.status = Some(match addons::run_captured(line) {
+        Ok(status) => status,
+        Err(e) => e.to_string(),
+    });
+
+    *list_rep = match ListRep::new(files.0, files.1) {
+        Ok(list_rep) => list_rep,
+        Err(e) => {
+            state.status = Some(format!("not reloaded: {}", e));
+            return;
+        }
+    };
+    notify(Hook::Reload, &list_rep.tasks, config, state);
+    state.contexts.reset(list_rep.filters.contexts.len());
+    state.tags.reset(list_rep.filters.tags.len());
+    // the lines may have moved, so marks and snapshots no longer apply
+    state.marked.clear();
+    state.undo.clear();
+}
+
+/// `run_script` runs a script command over the tasks, applying the lines it
+/// rewrote or added as one change which can be undone
+fn run_script(
+    selected: Option<usize>,
+    line: &str,
+    filters: &[String],
+    list_rep: &mut ListRep,
+    state: &mut State,
+) {
+    let outcome =
+        match state
+            .scripts
+            .command(line, &list_rep.tasks, selected, &state.marked, filters)
+        {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                state.status = Some(e);
+                return;
+            }
+        };
+
+    let changed = outcome.edits.len() + outcome.added.len();
+    if changed > 0 {
+        let snapshot = list_rep.snapshot();
+        for (i, line) in outcome.edits {
+            list_rep.tasks[i] = line;
+        }
+        list_rep.tasks.extend(outcome.added);
+        list_rep.modified = true;
+        list_rep.refresh_filters();
+        state.contexts.reset(list_rep.filters.contexts.len());
+        state.tags.reset(list_rep.filters.tags.len());
+        state.undo.push((format!("script {}", outcome.name), snapshot));
+    }
+    let name = &outcome.name;
+    state.status = Some(outcome.status.unwrap_or_else(|| {
+        format!(
+            "{} changed {} lines{}",
+            name,
+            changed,
+            if changed > 0 { ", u to undo" } else { "" }
+        )
+    }));
+}
+
+/// `recover` starts from the changes a crashed instance hadn't written,
+/// which can be undone to get back to the files as they are
+fn recover(changes: Changes, list_rep: &mut ListRep, state: &mut State) {
+    state.undo.push(("recovering".to_owned(), list_rep.snapshot()));
+    list_rep.tasks = changes.tasks;
+    list_rep.modified = true;
+    if let Some(done) = changes.done {
+        list_rep.done = done;
+        list_rep.done_modified = true;
+    }
+    list_rep.refresh_filters();
+    state.tasks.reset(list_rep.tasks.len());
+    state.contexts.reset(list_rep.filters.contexts.len());
+    state.tags.reset(list_rep.filters.tags.len());
+    state.status = Some("recovered the unsaved changes, w to write or u to undo".to_owned());
+}
+
+/// `restore_backup` replaces the tasks of the todo or done file with those
+/// in `backup`, which can be undone until the file is written
+fn restore_backup(backup: &Backup, file: Backed, list_rep: &mut ListRep, state: &mut State) {
+    let lines = match ListHandle::new(&backup.path).get_lines() {
+        Ok(lines) => lines,
+        Err(e) => {
+            state.status = Some(format!("not restored: {}", e));
+            return;
+        }
+    };
+
+    let description = format!("restoring the backup from {}", backup.taken.format("%c"));
+    info!("{}", description);
+    state.undo.push((description, list_rep.snapshot()));
+    match file {
+        Backed::Todo => {
+            list_rep.tasks = lines;
+            list_rep.modified = true;
+            list_rep.refresh_filters();
+            state.tasks.reset(list_rep.tasks.len());
+            state.contexts.reset(list_rep.filters.contexts.len());
+            state.tags.reset(list_rep.filters.tags.len());
+            state.marked.clear();
+        }
+        Backed::Done => {
+            list_rep.done = lines;
+            list_rep.done_modified = true;
+        }
+    }
+    state.view = ViewKind::Main;
+    state.status = Some("restored the backup, u to undo or w to write".to_owned());
+}
+
+/// `apply_batch` applies the edits which rewrite a context or project across
+/// both files, snapshotting them first so the whole batch can be undone.
+/// `selected` are the names of the active filters, which follow a rename.
+fn apply_batch(
+    action: Action,
+    list_rep: &mut ListRep,
+    state: &mut State,
+    selected: &mut Vec<String>,
+) {
+    let (from, to) = match action {
+        Action::Undo => {
+            state.status = Some(match state.undo.pop() {
+                Some((description, snapshot)) => {
+                    list_rep.restore(snapshot);
+                    format!("undid {}", description)
                 }
+                None => "nothing to undo".to_owned(),
+            });
+            state.contexts.reset(list_rep.filters.contexts.len());
+            state.tags.reset(list_rep.filters.tags.len());
+            return;
+        }
+        Action::RestoreBackup(backup, file) => {
+            restore_backup(&backup, file, list_rep, state);
+            return;
+        }
+        Action::Retag(from, to) => (from, to),
+        Action::Merge(from, to) => {
+            let names = if from.starts_with('@') {
+                &list_rep.filters.contexts
+            } else {
+                &list_rep.filters.tags
+            };
+            if to == from || !names.contains(&to) {
+                state.status = Some(format!("nothing named {} to merge into", to));
+                return;
             }
-            action => return Ok(action),
+            (from, Some(to))
+        }
+        _ => return,
+    };
+
+    if let Some(to) = &to {
+        if to.len() < 2 || to.contains(' ') || !to.starts_with(&from[..1]) {
+            state.status = Some(format!("{} must be one word starting with {}", to, &from[..1]));
+            return;
+        }
+    }
+
+    let snapshot = list_rep.snapshot();
+    let tasks = retag_all(&mut list_rep.tasks, &from, to.as_deref());
+    let done = retag_all(&mut list_rep.done, &from, to.as_deref());
+    if tasks + done == 0 {
+        return;
+    }
+    list_rep.modified |= tasks > 0;
+    list_rep.done_modified |= done > 0;
+    list_rep.refresh_filters();
+    state.contexts.reset(list_rep.filters.contexts.len());
+    state.tags.reset(list_rep.filters.tags.len());
+
+    let description = match &to {
+        Some(to) => format!("{} → {}", from, to),
+        None => format!("stripping {}", from),
+    };
+    state.status = Some(format!(
+        "{} on {} lines, u to undo",
+        description,
+        tasks + done
+    ));
+    state.undo.push((description, snapshot));
+
+    match to {
+        Some(to) => {
+            for name in selected.iter_mut().filter(|name| **name == from) {
+                name.clone_from(&to);
+            }
+        }
+        None => selected.retain(|name| *name != from),
+    }
+}
+
+/// `retag_all` rewrites `from` in each of `lines`, returning how many changed
+fn retag_all(lines: &mut [String], from: &str, to: Option<&str>) -> usize {
+    let mut changed = 0;
+    for line in lines {
+        if let Some(retagged) = edit::retag(line, from, to) {
+            *line = retagged;
+            changed += 1;
         }
     }
+    changed
+}
+
+/// `selected_filters` are the names of the active filters
+fn selected_filters(active_filters: &Filters<BTreeSet<&str>>) -> Vec<String> {
+    active_filters
+        .contexts
+        .iter()
+        .chain(&active_filters.tags)
+        .map(|&name| name.to_owned())
+        .collect()
+}
+
+/// `select_filters` makes the filters named in `names` active again after
+/// `source` was rebuilt. A filter on a parent node is kept while any value
+/// is still nested under it.
+fn select_filters<'a>(
+    source: &'a Filters<Vec<String>>,
+    names: &[String],
+) -> Filters<BTreeSet<&'a str>> {
+    let pick = |list: &'a [String]| {
+        names
+            .iter()
+            .filter_map(|name| {
+                list.iter()
+                    .find(|value| value.starts_with(&name[..]))
+                    .map(|value| &value[..name.len()])
+            })
+            .collect()
+    };
+    Filters::new(pick(&source.contexts), pick(&source.tags))
+}
+
+/// `toggle_filter` turns the filter on the first `len` bytes of the `i`th
+/// context or tag on or off, which is all of it unless a parent node of the
+/// sidebar tree was selected
+fn toggle_filter<'a>(
+    active_filters: &mut Filters<BTreeSet<&'a str>>,
+    filter_source: &'a Filters<Vec<String>>,
+    active_list: ActiveList,
+    i: usize,
+    len: usize,
+) {
+    // tasks are toggled with Action::Complete instead
+    let Some(kind) = active_list.filter() else {
+        return;
+    };
+
+    let filters = active_filters.get_mut(kind);
+    let filter = &filter_source.get(kind)[i][..len];
+    if !filters.remove(filter) {
+        filters.insert(filter);
+    }
+}
+
+fn parse_all(tasks: &[String]) -> Vec<ParsedLine<'_>> {
+    tasks
+        .iter()
+        .enumerate()
+        .map(|(i, task)| ParsedLine::new(&task[..], i))
+        .collect()
+}
+
+/// `filter_items` parses the tasks matching the active filters. Deferred
+/// tasks are left out of the main view unless it's been asked to show them,
+/// as are blocked tasks when it only lists actionable ones.
+fn filter_items<'a>(
+    tasks: &'a [String],
+    active_filters: &Filters<BTreeSet<&str>>,
+    state: &State,
+    deps: &Deps,
+) -> Vec<ParsedLine<'a>> {
+    let main = state.view == ViewKind::Main;
+    let hide_deferred = main && !state.show_deferred;
+    let hide_blocked = main && state.actionable_only;
+    tasks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, task)| {
+            if !active_filters.include(task) {
+                return None;
+            }
+            let item = ParsedLine::new(&task[..], i);
+            if hide_deferred && is_deferred(&item, state.today) {
+                return None;
+            }
+            if hide_blocked && deps.is_blocked(i) {
+                return None;
+            }
+            Some(item)
+        })
+        .collect()
+}
+
+/// `is_deferred` is true for tasks whose threshold date hasn't been reached
+fn is_deferred(item: &ParsedLine, today: NaiveDate) -> bool {
+    item.threshold_date
+        .and_then(date::from_iso)
+        .is_some_and(|t| t > today)
 }
 
-fn run_with_view<B: Backend>(
+/// `set_date` resolves `expr` and stores it on the line as the `key`
+/// extension. It returns whether the line was changed, leaving a status
+/// message if the expression isn't a recognisable date.
+fn set_date(
+    line: &mut String,
+    key: &str,
+    expr: &str,
+    clock: &dyn Clock,
+    state: &mut State,
+) -> bool {
+    if let Some(d) = date::parse(expr, clock) {
+        *line = edit::set_extension(line, key, &d.format(date::FORMAT).to_string());
+        true
+    } else {
+        state.status = Some(format!("unrecognised date: {}", expr));
+        false
+    }
+}
+
+fn run_with_view<B: Backend, V: View>(
     terminal: &mut Terminal<B>,
     eventgen: &Generator,
-    mut main_view: MainView,
-) -> Result<Action, Box<dyn Error>> {
+    mut view: V,
+) -> Result<Action, Error> {
     let res = loop {
-        terminal.draw(|f| main_view.draw(f))?;
+        terminal.draw(|f| view.draw(f))?;
 
-        if let Some(action) = main_view.handle(eventgen.next()?) {
+        let event = eventgen
+            .next()
+            .map_err(|_| io::Error::new(ErrorKind::BrokenPipe, "input closed"))?;
+        let key = match event {
+            Event::Input(key) => Some(key),
+            Event::Tick => None,
+        };
+        if let Some(action) = view.handle(event) {
+            if let Some(key) = key {
+                debug!("{:?} -> {:?}", key, action);
+            }
             break action;
         }
     };
@@ -153,15 +1107,107 @@ fn run_with_view<B: Backend>(
     Ok(res)
 }
 
-fn make_view_strings(input_list: &[String], filters: &BTreeSet<&str>) -> Vec<String> {
-    input_list
-        .iter()
-        .map(|v| {
-            format!(
-                "[{}] {}",
-                if filters.contains(&v[..]) { "x" } else { " " },
-                &v[1..],
-            )
-        })
-        .collect()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    /// `scratch` is an empty directory of its own for each test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("todotui-rs-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// `rep` loads a todo file holding `lines`, with no done file
+    fn rep(dir: &Path, lines: &str) -> ListRep {
+        let path = dir.join("todo.txt");
+        fs::write(&path, lines).unwrap();
+        ListRep::new(
+            &ListHandle::new(&path),
+            &ListHandle::new(&dir.join("done.txt")),
+        )
+        .unwrap()
+    }
+
+    fn state() -> State {
+        State::new(
+            0,
+            0,
+            0,
+            false,
+            NaiveDate::from_ymd_opt(2020, 10, 14).unwrap(),
+        )
+    }
+
+    #[test]
+    fn undo_puts_back_a_batch() {
+        let dir = scratch("undo-batch");
+        let mut list_rep = rep(&dir, "call bob @home\n");
+        let mut state = state();
+        let retag = Action::Retag("@home".to_owned(), Some("@work".to_owned()));
+        apply_batch(retag, &mut list_rep, &mut state, &mut Vec::new());
+        assert_eq!(list_rep.tasks, ["call bob @work"]);
+
+        apply_batch(Action::Undo, &mut list_rep, &mut state, &mut Vec::new());
+        assert_eq!(list_rep.tasks, ["call bob @home"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn an_edit_after_a_batch_isnt_undone() {
+        let dir = scratch("undo-edit");
+        let mut list_rep = rep(&dir, "call bob @home\n");
+        let mut state = state();
+        let today = state.today;
+        let retag = Action::Retag("@home".to_owned(), Some("@work".to_owned()));
+        apply_batch(retag, &mut list_rep, &mut state, &mut Vec::new());
+        let edit = Action::Edit(0, "call alice @work".to_owned());
+        let unhandled = apply_edit(
+            edit,
+            &mut list_rep.tasks,
+            &mut list_rep.modified,
+            &mut state,
+            &Config::default(),
+            &today,
+        );
+        assert!(unhandled.is_none());
+
+        apply_batch(Action::Undo, &mut list_rep, &mut state, &mut Vec::new());
+        assert_eq!(list_rep.tasks, ["call alice @work"]);
+        assert_eq!(state.status.as_deref(), Some("nothing to undo"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restoring_a_backup_can_be_undone() {
+        let dir = scratch("restore");
+        let mut list_rep = rep(&dir, "call bob\n");
+        let mut state = state();
+        let backup = Backup {
+            path: dir.join("backup"),
+            taken: state.today.and_hms_opt(9, 0, 0).unwrap(),
+        };
+        fs::write(&backup.path, "call alice\nx 2020-10-12 pay rent\n").unwrap();
+
+        let restore = Action::RestoreBackup(backup.clone(), Backed::Todo);
+        apply_batch(restore, &mut list_rep, &mut state, &mut Vec::new());
+        assert_eq!(list_rep.tasks, ["call alice", "x 2020-10-12 pay rent"]);
+        assert!(list_rep.modified);
+        apply_batch(Action::Undo, &mut list_rep, &mut state, &mut Vec::new());
+        assert_eq!(list_rep.tasks, ["call bob"]);
+
+        let restore = Action::RestoreBackup(backup, Backed::Done);
+        apply_batch(restore, &mut list_rep, &mut state, &mut Vec::new());
+        assert_eq!(list_rep.tasks, ["call bob"]);
+        assert_eq!(list_rep.done, ["call alice", "x 2020-10-12 pay rent"]);
+        assert!(list_rep.done_modified);
+        apply_batch(Action::Undo, &mut list_rep, &mut state, &mut Vec::new());
+        assert!(list_rep.done.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
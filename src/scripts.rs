@@ -0,0 +1,493 @@
+use crate::{
+    config,
+    todo::{date, ParsedLine},
+};
+
+use chrono::NaiveDate;
+use log::{info, warn};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST, INT};
+use std::{
+    cell::Cell,
+    collections::{BTreeSet, HashMap},
+    convert::TryFrom,
+    fs,
+    rc::Rc,
+};
+
+/// how many operations one call into a script may take, so a runaway loop
+/// stops with an error instead of hanging the interface
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// the prefix of the functions run as commands from the script prompt
+const COMMAND_PREFIX: &str = "cmd_";
+
+/// `Scripts` are the Rhai scripts kept in the `scripts` directory of the
+/// config directory. Each task is handed to them as a map of its parts, and
+/// they can define any of:
+///
+/// - `urgency(task)`, a number the tasks are listed by, highest first
+/// - `column(task)`, text shown after each task in the main view
+/// - `visible(task)`, false for the tasks to leave out of every view
+/// - `cmd_<name>(args)`, a command run as `name args...` from the script
+///   prompt, with `this` holding the tasks, the selection and the filters
+///
+/// A function which fails is reported in the status bar and left alone
+/// until the scripts are reloaded.
+pub struct Scripts {
+    engine: Engine,
+    ast: AST,
+    /// the number of parameters each function takes
+    functions: HashMap<String, usize>,
+    broken: BTreeSet<String>,
+    /// read by `today()` and `days_until()` in the scripts
+    today: Rc<Cell<NaiveDate>>,
+}
+
+/// `Outcome` is what a command changed, to be applied as a single edit
+pub struct Outcome {
+    pub name: String,
+    /// file indices of the tasks rewritten, with their new lines
+    pub edits: Vec<(usize, String)>,
+    pub added: Vec<String>,
+    pub status: Option<String>,
+}
+
+impl Scripts {
+    pub fn new(today: NaiveDate) -> Self {
+        let today = Rc::new(Cell::new(today));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let now = Rc::clone(&today);
+        engine.register_fn("today", move || now.get().format(date::FORMAT).to_string());
+        let now = Rc::clone(&today);
+        engine.register_fn("days_until", move |d: &str| -> Dynamic {
+            date::from_iso(d).map_or(Dynamic::UNIT, |d| (d - now.get()).num_days().into())
+        });
+
+        Self {
+            engine,
+            ast: AST::empty(),
+            functions: HashMap::new(),
+            broken: BTreeSet::new(),
+            today,
+        }
+    }
+
+    /// `load` compiles every `.rhai` file in the scripts directory, in order
+    /// of their names
+    pub fn load(today: NaiveDate) -> Result<Self, String> {
+        let mut scripts = Self::new(today);
+        let dir = config::dir().join("scripts");
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return Ok(scripts);
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| scripts.compile(&text))
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        Ok(scripts)
+    }
+
+    /// `compile` adds the functions of the script `text` to those already
+    /// loaded
+    fn compile(&mut self, text: &str) -> Result<(), String> {
+        let ast = self.engine.compile(text).map_err(|e| e.to_string())?;
+        self.ast.combine(ast);
+        self.functions = self
+            .ast
+            .iter_functions()
+            .map(|f| (f.name.to_owned(), f.params.len()))
+            .collect();
+        Ok(())
+    }
+
+    fn defines(&self, name: &str, params: usize) -> bool {
+        self.functions.get(name) == Some(&params) && !self.broken.contains(name)
+    }
+
+    /// `call` runs the script function `name`, putting any error in `status`
+    /// and leaving the function alone from then on
+    fn call(
+        &mut self,
+        name: &str,
+        this: Option<&mut Dynamic>,
+        args: Vec<Dynamic>,
+        status: &mut Option<String>,
+    ) -> Option<Dynamic> {
+        let mut options = CallFnOptions::new().eval_ast(false);
+        if let Some(this) = this {
+            options = options.bind_this_ptr(this);
+        }
+        let result =
+            self.engine
+                .call_fn_with_options(options, &mut Scope::new(), &self.ast, name, args);
+
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!("script {}: {}", name, e);
+                self.broken.insert(name.to_owned());
+                *status = Some(format!("script {}: {}", name, e));
+                None
+            }
+        }
+    }
+
+    fn call_on_task(
+        &mut self,
+        name: &str,
+        item: &ParsedLine,
+        status: &mut Option<String>,
+    ) -> Option<Dynamic> {
+        if !self.defines(name, 1) {
+            return None;
+        }
+        self.call(name, None, vec![task_map(item).into()], status)
+    }
+
+    /// `arrange` leaves out the tasks `visible` rejects and orders the rest
+    /// by `urgency`, keeping their order otherwise
+    pub fn arrange(
+        &mut self,
+        items: &mut Vec<ParsedLine>,
+        today: NaiveDate,
+        status: &mut Option<String>,
+    ) {
+        self.today.set(today);
+        if self.defines("visible", 1) {
+            items.retain(|item| {
+                self.call_on_task("visible", item, status)
+                    .and_then(|visible| visible.as_bool().ok())
+                    .unwrap_or(true)
+            });
+        }
+
+        if self.defines("urgency", 1) {
+            let mut ranked: Vec<(f64, ParsedLine)> = items
+                .drain(..)
+                .map(|item| {
+                    let urgency = self
+                        .call_on_task("urgency", &item, status)
+                        .and_then(|u| as_number(&u))
+                        .unwrap_or(0.0);
+                    (urgency, item)
+                })
+                .collect();
+            ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+            items.extend(ranked.into_iter().map(|(_, item)| item));
+        }
+    }
+
+    /// `columns` is the text `column` gives for each of `items`
+    pub fn columns(
+        &mut self,
+        items: &[ParsedLine],
+        status: &mut Option<String>,
+    ) -> Vec<Option<String>> {
+        items
+            .iter()
+            .map(|item| {
+                self.call_on_task("column", item, status)
+                    .map(|value| value.to_string())
+                    .filter(|text| !text.is_empty())
+            })
+            .collect()
+    }
+
+    /// `command_names` are the commands the scripts define
+    fn command_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .functions
+            .keys()
+            .filter_map(|name| name.strip_prefix(COMMAND_PREFIX))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// `command` runs the command named by the first word of `line` with the
+    /// rest as its arguments. `this` is a map holding:
+    ///
+    /// - `tasks`, every task, whose `raw` line the command may rewrite
+    /// - `selected`, the index of the selected task, if any
+    /// - `marked`, the indices of the marked tasks
+    /// - `filters`, the active contexts and projects
+    /// - `add`, lines to add as new tasks
+    /// - `status`, a message for the status bar
+    pub fn command(
+        &mut self,
+        line: &str,
+        tasks: &[String],
+        selected: Option<usize>,
+        marked: &BTreeSet<usize>,
+        filters: &[String],
+    ) -> Result<Outcome, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((name, args)) = words.split_first() else {
+            return Err(match self.command_names() {
+                names if names.is_empty() => "no script commands defined".to_owned(),
+                names => format!("script commands: {}", names.join(", ")),
+            });
+        };
+        let function = format!("{}{}", COMMAND_PREFIX, name);
+        if !self.functions.contains_key(&function) {
+            return Err(format!("no script command named {}", name));
+        }
+
+        let index = |i: usize| Dynamic::from(INT::try_from(i).unwrap_or(INT::MAX));
+        let mut this = Map::new();
+        this.insert(
+            "tasks".into(),
+            tasks
+                .iter()
+                .enumerate()
+                .map(|(i, task)| Dynamic::from(task_map(&ParsedLine::new(task, i))))
+                .collect::<Array>()
+                .into(),
+        );
+        this.insert("selected".into(), selected.map_or(Dynamic::UNIT, index));
+        this.insert(
+            "marked".into(),
+            marked.iter().map(|&i| index(i)).collect::<Array>().into(),
+        );
+        this.insert(
+            "filters".into(),
+            strings(filters.iter().map(|f| &f[..])).into(),
+        );
+        this.insert("add".into(), Array::new().into());
+        this.insert("status".into(), Dynamic::UNIT);
+
+        let mut this = Dynamic::from(this);
+        info!("running the script command {} {:?}", name, args);
+        let args = strings(args.iter().copied());
+        // the command may fail without being disabled, as it only runs when asked
+        self.broken.remove(&function);
+        let mut error = None;
+        self.call(&function, Some(&mut this), vec![args.into()], &mut error);
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        read_outcome(name, tasks, &this)
+    }
+}
+
+/// `read_outcome` collects what a command changed in `this`
+fn read_outcome(name: &str, tasks: &[String], this: &Dynamic) -> Result<Outcome, String> {
+    let malformed = |what: &str| format!("script {}: {}", name, what);
+    let this = this
+        .read_lock::<Map>()
+        .ok_or_else(|| malformed("this is no longer a map"))?;
+    let field = |key: &str| this.get(key).cloned().unwrap_or(Dynamic::UNIT);
+    let lines = |key: &str| -> Option<Vec<String>> {
+        field(key)
+            .into_typed_array::<Dynamic>()
+            .ok()?
+            .into_iter()
+            .map(|line| line.into_string().ok().filter(|l| !l.contains('\n')))
+            .collect()
+    };
+
+    let new_tasks = field("tasks")
+        .into_typed_array::<Dynamic>()
+        .ok()
+        .filter(|t| t.len() == tasks.len())
+        .ok_or_else(|| malformed("tasks can't be added or removed, push lines to this.add"))?;
+    let mut edits = Vec::new();
+    for (i, task) in new_tasks.into_iter().enumerate() {
+        let raw = task
+            .try_cast::<Map>()
+            .and_then(|t| t.get("raw").cloned())
+            .and_then(|raw| raw.into_string().ok())
+            .filter(|raw| !raw.contains('\n'))
+            .ok_or_else(|| malformed("each task needs a single line of raw text"))?;
+        if raw != tasks[i] {
+            edits.push((i, raw));
+        }
+    }
+
+    Ok(Outcome {
+        name: name.to_owned(),
+        edits,
+        added: lines("add").ok_or_else(|| malformed("this.add must hold lines of text"))?,
+        status: field("status").into_string().ok(),
+    })
+}
+
+fn strings<'a>(values: impl Iterator<Item = &'a str>) -> Array {
+    values
+        .map(|value| Dynamic::from(value.to_owned()))
+        .collect()
+}
+
+/// `as_number` reads the integer or float a script returned
+fn as_number(value: &Dynamic) -> Option<f64> {
+    value.as_float().ok().or_else(|| {
+        value
+            .as_int()
+            .ok()
+            .and_then(|i| i32::try_from(i).ok())
+            .map(f64::from)
+    })
+}
+
+/// `task_map` is how a task is seen by the scripts
+fn task_map(item: &ParsedLine) -> Map {
+    let text = |value: Option<&str>| value.map_or(Dynamic::UNIT, |v| v.to_owned().into());
+
+    let mut map = Map::new();
+    map.insert("raw".into(), item.raw.to_owned().into());
+    map.insert(
+        "index".into(),
+        INT::try_from(item.index).unwrap_or(INT::MAX).into(),
+    );
+    map.insert("body".into(), item.body.clone().into());
+    map.insert("complete".into(), item.complete.into());
+    map.insert(
+        "priority".into(),
+        item.priority
+            .map_or(Dynamic::UNIT, |p| p.to_string().into()),
+    );
+    map.insert("created".into(), text(item.start_date));
+    map.insert("completed".into(), text(item.completion_date));
+    map.insert("due".into(), text(item.due_date));
+    map.insert("threshold".into(), text(item.threshold_date));
+    map.insert("hidden".into(), item.hidden.into());
+    map.insert(
+        "contexts".into(),
+        strings(item.contexts.iter().copied()).into(),
+    );
+    map.insert("projects".into(), strings(item.tags.iter().copied()).into());
+    map.insert("id".into(), text(item.id()));
+    map.insert("parent".into(), text(item.parent()));
+    map.insert("deps".into(), strings(item.deps().into_iter()).into());
+    map.insert(
+        "extensions".into(),
+        item.extensions
+            .iter()
+            .map(|&(k, v)| (k.into(), v.to_owned().into()))
+            .collect::<Map>()
+            .into(),
+    );
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scripts(text: &str) -> Scripts {
+        let mut scripts = Scripts::new(NaiveDate::from_ymd_opt(2020, 10, 14).unwrap());
+        scripts.compile(text).unwrap();
+        scripts
+    }
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|&l| l.to_owned()).collect()
+    }
+
+    #[test]
+    fn commands_edit_and_add_lines() {
+        let mut scripts = scripts(
+            r#"
+            fn cmd_tag(args) {
+                let i = this.selected;
+                this.tasks[i].raw += " " + args[0];
+                this.add.push("follow up " + args[0]);
+                this.status = "tagged";
+            }
+            fn cmd_nothing(args) {}
+            "#,
+        );
+        let tasks = lines(&["call bob", "call alice"]);
+        let marked = BTreeSet::new();
+
+        let outcome = scripts
+            .command("tag +phone", &tasks, Some(1), &marked, &[])
+            .unwrap();
+        assert_eq!(outcome.name, "tag");
+        assert_eq!(outcome.edits, [(1, "call alice +phone".to_owned())]);
+        assert_eq!(outcome.added, ["follow up +phone"]);
+        assert_eq!(outcome.status.as_deref(), Some("tagged"));
+
+        let outcome = scripts
+            .command("nothing", &tasks, None, &marked, &[])
+            .unwrap();
+        assert!(outcome.edits.is_empty() && outcome.added.is_empty());
+        assert_eq!(outcome.status, None);
+    }
+
+    #[test]
+    fn commands_which_cant_be_applied() {
+        let mut scripts = scripts(
+            r#"
+            fn cmd_drop(args) { this.tasks.pop(); }
+            fn cmd_split(args) { this.tasks[0].raw = "a\nb"; }
+            fn cmd_fail(args) { throw "no"; }
+            "#,
+        );
+        let tasks = lines(&["call bob"]);
+        let run = |scripts: &mut Scripts, line: &str| {
+            scripts
+                .command(line, &tasks, None, &BTreeSet::new(), &[])
+                .err()
+        };
+
+        assert_eq!(
+            run(&mut scripts, "").as_deref(),
+            Some("script commands: drop, fail, split")
+        );
+        assert_eq!(
+            run(&mut scripts, "missing").as_deref(),
+            Some("no script command named missing")
+        );
+        assert!(run(&mut scripts, "drop").is_some_and(|e| e.contains("can't be added or removed")));
+        assert!(run(&mut scripts, "split").is_some_and(|e| e.contains("single line")));
+        assert!(run(&mut scripts, "fail").is_some_and(|e| e.starts_with("script cmd_fail")));
+        // a command isn't disabled by failing, as it only runs when asked
+        assert!(run(&mut scripts, "fail").is_some_and(|e| e.starts_with("script cmd_fail")));
+    }
+
+    #[test]
+    fn arranges_by_urgency_and_visibility() {
+        let mut scripts = scripts(
+            r#"
+            fn visible(task) { !task.complete }
+            fn urgency(task) { if task.priority == "A" { 2 } else if task.due != () { 1.5 } else { 0 } }
+            fn column(task) { if task.due != () { days_until(task.due) + "d" } else { "" } }
+            "#,
+        );
+        let tasks = lines(&[
+            "call bob",
+            "x 2020-10-12 pay rent",
+            "file taxes due:2020-10-16",
+            "(A) write report",
+        ]);
+        let mut items: Vec<ParsedLine> = tasks
+            .iter()
+            .enumerate()
+            .map(|(i, t)| ParsedLine::new(t, i))
+            .collect();
+        let mut status = None;
+        let today = scripts.today.get();
+        scripts.arrange(&mut items, today, &mut status);
+
+        let order: Vec<usize> = items.iter().map(|item| item.index).collect();
+        assert_eq!(order, [3, 2, 0]);
+        assert_eq!(
+            scripts.columns(&items, &mut status),
+            [None, Some("2d".to_owned()), None]
+        );
+        assert_eq!(status, None);
+    }
+}
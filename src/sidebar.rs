@@ -0,0 +1,386 @@
+use crate::{
+    app::State,
+    filters::Filters,
+    todo::{date, ListRep, ParsedLine},
+};
+
+use chrono::NaiveDate;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap},
+};
+
+/// `Order` is how the context and tag sidebars are sorted
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Order {
+    Name,
+    /// most open tasks first
+    Count,
+    /// most recently created or completed first
+    Recent,
+}
+
+impl Order {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "name" => Some(Order::Name),
+            "count" => Some(Order::Count),
+            "recent" => Some(Order::Recent),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Order::Name => "name",
+            Order::Count => "count",
+            Order::Recent => "recent",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Order::Name => Order::Count,
+            Order::Count => Order::Recent,
+            Order::Recent => Order::Name,
+        }
+    }
+}
+
+/// Entry is a line of a sidebar
+pub struct Entry {
+    /// the position among all the contexts or tags of the value filtered on
+    pub index: usize,
+    /// how much of the value is filtered on, which is less than all of it
+    /// for a parent node
+    pub len: usize,
+    /// the node in the sidebar tree, which is what's collapsed
+    pub path: String,
+    pub label: String,
+}
+
+/// `Usage` is how often and how lately each context and tag is used, counted
+/// for every node of the tree so a parent covers everything nested under it
+struct Usage<'a> {
+    /// open tasks, only those matching the active filters when faceted
+    open: HashMap<&'a str, usize>,
+    recent: HashMap<&'a str, NaiveDate>,
+}
+
+/// `Node` is a context or tag in the sidebar tree, or a parent shared by
+/// several of them which isn't used on its own
+struct Node<'a> {
+    /// the position among all the contexts or tags of a value under the node
+    index: usize,
+    /// what's filtered on when the node is selected. A parent which isn't
+    /// itself a value keeps the separator, so it only matches nested values.
+    filter: &'a str,
+    children: Vec<&'a str>,
+}
+
+/// `nodes` are the paths leading up to `value` when split on `separator`,
+/// ending with `value` itself
+fn nodes<'a>(value: &'a str, separator: &str) -> impl Iterator<Item = &'a str> {
+    let parents: Vec<&str> = if separator.is_empty() {
+        Vec::new()
+    } else {
+        value
+            .match_indices(separator)
+            .map(|(i, _)| &value[..i])
+            // the sigil alone isn't a parent
+            .filter(|parent| parent.len() > 1)
+            .collect()
+    };
+    parents.into_iter().chain(Some(value))
+}
+
+/// `make_filter_views` builds the context and tag sidebars as trees split on
+/// the configured separator, each node shown with its count of open tasks.
+/// When faceted only the nodes found on tasks matching the active filters
+/// are listed, along with the active ones.
+pub fn make_filter_views(
+    list_rep: &ListRep,
+    active_filters: &Filters<BTreeSet<&str>>,
+    state: &State,
+) -> Filters<Vec<Entry>> {
+    let separator = &state.sidebar.separator[..];
+    let mut usage = Usage {
+        open: HashMap::new(),
+        recent: HashMap::new(),
+    };
+
+    for (i, line) in list_rep.tasks.iter().chain(&list_rep.done).enumerate() {
+        let item = ParsedLine::new(line, i);
+        let latest = item
+            .completion_date
+            .into_iter()
+            .chain(item.start_date)
+            .filter_map(date::from_iso)
+            .max();
+        let counted = !item.complete && (!state.sidebar.faceted || active_filters.include(line));
+
+        // a task under two values sharing a parent only counts once for it
+        let paths: BTreeSet<&str> = item
+            .contexts
+            .iter()
+            .chain(&item.tags)
+            .flat_map(|name| nodes(name, separator))
+            .collect();
+        for path in paths {
+            if let Some(latest) = latest {
+                let recent = usage.recent.entry(path).or_insert(latest);
+                *recent = latest.max(*recent);
+            }
+            if counted {
+                *usage.open.entry(path).or_default() += 1;
+            }
+        }
+    }
+
+    Filters::new(
+        make_view_strings(
+            &list_rep.filters.contexts,
+            &active_filters.contexts,
+            &usage,
+            state,
+        ),
+        make_view_strings(&list_rep.filters.tags, &active_filters.tags, &usage, state),
+    )
+}
+
+fn make_view_strings(
+    input_list: &[String],
+    filters: &BTreeSet<&str>,
+    usage: &Usage,
+    state: &State,
+) -> Vec<Entry> {
+    let separator = &state.sidebar.separator[..];
+    let mut tree: BTreeMap<&str, Node> = BTreeMap::new();
+    let mut roots = Vec::new();
+
+    for (index, value) in input_list.iter().enumerate() {
+        let mut parent: Option<&str> = None;
+        for path in nodes(value, separator) {
+            if !tree.contains_key(path) {
+                match parent.and_then(|parent| tree.get_mut(parent)) {
+                    Some(parent) => parent.children.push(path),
+                    None => roots.push(path),
+                }
+                tree.insert(
+                    path,
+                    Node {
+                        index,
+                        filter: &value[..(path.len() + separator.len()).min(value.len())],
+                        children: Vec::new(),
+                    },
+                );
+            }
+            parent = Some(path);
+        }
+        // a value listed after its nested ones is filtered on as itself
+        if let Some(node) = tree.get_mut(&value[..]) {
+            node.index = index;
+            node.filter = value;
+        }
+    }
+
+    let mut entries = Vec::new();
+    add_entries(&mut entries, &tree, roots, 0, filters, usage, state);
+    entries
+}
+
+/// `add_entries` lists the nodes at `paths` and, unless they're collapsed,
+/// everything nested under them
+fn add_entries(
+    entries: &mut Vec<Entry>,
+    tree: &BTreeMap<&str, Node>,
+    mut paths: Vec<&str>,
+    depth: usize,
+    filters: &BTreeSet<&str>,
+    usage: &Usage,
+    state: &State,
+) {
+    let count = |path: &str| usage.open.get(path).copied().unwrap_or(0);
+
+    // the paths come in alphabetically, which the sorts fall back on
+    match state.sidebar.order {
+        Order::Name => {}
+        Order::Count => paths.sort_by_key(|&path| Reverse(count(path))),
+        Order::Recent => paths.sort_by_key(|&path| Reverse(usage.recent.get(path))),
+    }
+
+    for path in paths {
+        let node = &tree[path];
+        let active = filters.contains(node.filter);
+        let holds_active = filters.iter().any(|f| f.starts_with(path));
+        if state.sidebar.faceted && count(path) == 0 && !holds_active {
+            continue;
+        }
+
+        let collapsed = state.collapsed.contains(path);
+        let marker = match (node.children.is_empty(), collapsed) {
+            (true, _) => "",
+            (false, true) => "▸ ",
+            (false, false) => "▾ ",
+        };
+        // nested nodes only show the last part of their path
+        let name = if depth == 0 {
+            &path[1..]
+        } else {
+            path.rsplit(&state.sidebar.separator[..])
+                .next()
+                .unwrap_or(path)
+        };
+
+        entries.push(Entry {
+            index: node.index,
+            len: node.filter.len(),
+            path: path.to_owned(),
+            label: format!(
+                "{}[{}] {}{} {}",
+                "  ".repeat(depth),
+                if active { "x" } else { " " },
+                marker,
+                name,
+                count(path),
+            ),
+        });
+
+        if !collapsed {
+            let children = node.children.clone();
+            add_entries(entries, tree, children, depth + 1, filters, usage, state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::ListHandle;
+    use std::{fs, process};
+
+    /// `list_rep` loads a todo file holding `lines`
+    fn list_rep(name: &str, lines: &[&str]) -> ListRep {
+        let dir = std::env::temp_dir().join(format!("todotui-rs-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("todo.txt");
+        fs::write(&path, lines.join("\n")).unwrap();
+        let list_rep = ListRep::new(
+            &ListHandle::new(&path),
+            &ListHandle::new(&dir.join("done.txt")),
+        )
+        .unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        list_rep
+    }
+
+    /// `sidebars` are the labels of the context and tag sidebars over a todo
+    /// file holding `lines`
+    fn sidebars(
+        name: &str,
+        lines: &[&str],
+        active: &Filters<BTreeSet<&str>>,
+        state: &State,
+    ) -> (Vec<String>, Vec<String>) {
+        let list_rep = list_rep(name, lines);
+        let views = make_filter_views(&list_rep, active, state);
+        let labels =
+            |entries: Vec<Entry>| -> Vec<String> { entries.into_iter().map(|e| e.label).collect() };
+        (labels(views.contexts), labels(views.tags))
+    }
+
+    fn state(separator: &str) -> State {
+        let mut state = State::new(
+            0,
+            0,
+            0,
+            false,
+            NaiveDate::from_ymd_opt(2020, 10, 14).unwrap(),
+        );
+        separator.clone_into(&mut state.sidebar.separator);
+        state
+    }
+
+    fn no_filters<'a>() -> Filters<BTreeSet<&'a str>> {
+        Filters::new(BTreeSet::new(), BTreeSet::new())
+    }
+
+    const LINES: [&str; 4] = [
+        "2020-10-13 call bob @phone +work",
+        "2020-10-05 email alice @mail +work",
+        "write report @desk +home",
+        "x 2020-10-12 pay rent @desk +home",
+    ];
+
+    #[test]
+    fn counts_open_tasks() {
+        let (contexts, tags) = sidebars("sidebar-counts", &LINES, &no_filters(), &state(""));
+        assert_eq!(contexts, ["[ ] desk 1", "[ ] mail 1", "[ ] phone 1"]);
+        assert_eq!(tags, ["[ ] home 1", "[ ] work 2"]);
+    }
+
+    #[test]
+    fn sorts_by_count_or_recent_use() {
+        let mut state = state("");
+        state.sidebar.order = Order::Count;
+        let (_, tags) = sidebars("sidebar-count", &LINES, &no_filters(), &state);
+        assert_eq!(tags, ["[ ] work 2", "[ ] home 1"]);
+
+        state.sidebar.order = Order::Recent;
+        let (contexts, _) = sidebars("sidebar-recent", &LINES, &no_filters(), &state);
+        assert_eq!(contexts, ["[ ] phone 1", "[ ] desk 1", "[ ] mail 1"]);
+    }
+
+    #[test]
+    fn faceting_narrows_to_the_active_filters() {
+        let mut state = state("");
+        state.sidebar.faceted = true;
+        let mut active = no_filters();
+        active.tags.insert("+work");
+        let (contexts, tags) = sidebars("sidebar-faceted", &LINES, &active, &state);
+        assert_eq!(contexts, ["[ ] mail 1", "[ ] phone 1"]);
+        assert_eq!(tags, ["[x] work 2"]);
+    }
+
+    #[test]
+    fn nested_names_make_a_tree() {
+        let lines = [
+            "a +work.infra",
+            "b +work.docs +work.infra",
+            "c +work",
+            "d +home +proj.site",
+        ];
+        let (_, tags) = sidebars("sidebar-tree", &lines, &no_filters(), &state("."));
+        assert_eq!(
+            tags,
+            [
+                "[ ] home 1",
+                "[ ] ▾ proj 1",
+                "  [ ] site 1",
+                "[ ] ▾ work 3",
+                "  [ ] docs 1",
+                "  [ ] infra 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn collapsed_nodes_hide_whats_under_them() {
+        let lines = ["a +work.infra", "b +work.docs"];
+        let mut state = state(".");
+        state.collapsed.insert("+work".to_owned());
+        let (_, tags) = sidebars("sidebar-collapsed", &lines, &no_filters(), &state);
+        assert_eq!(tags, ["[ ] ▸ work 2"]);
+    }
+
+    #[test]
+    fn a_parent_filters_on_whats_nested() {
+        let list_rep = list_rep("sidebar-filter", &["a +work.infra", "b +home"]);
+        let views = make_filter_views(&list_rep, &no_filters(), &state("."));
+        let work = &views.tags[1];
+        assert_eq!(work.path, "+work");
+        // the separator is kept, so +workshop wouldn't match
+        let value = &list_rep.filters.tags[work.index];
+        assert_eq!(&value[..work.len], "+work.");
+    }
+}
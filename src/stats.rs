@@ -0,0 +1,394 @@
+use crate::{
+    app::{View, ViewKind},
+    event::{Event, Handler as EventHandler},
+    runner::Action,
+    todo::{date, ParsedLine},
+};
+
+use chrono::{Datelike, Duration, NaiveDate};
+use std::{collections::HashMap, convert::TryFrom};
+use termion::event::Key;
+use tui::{
+    backend::Backend,
+    layout::{
+        Constraint::{Length, Min, Percentage},
+        Direction, Layout, Rect,
+    },
+    style::{Color, Style},
+    symbols,
+    text::{Span, Spans},
+    widgets::{Axis, BarChart, Block, Borders, Chart, Dataset, GraphType, Paragraph, Sparkline},
+    Frame,
+};
+
+/// how many days the daily series cover, ending today
+const DAYS: u32 = 30;
+/// how many weeks of completions are charted, ending this week
+const WEEKS: u32 = 8;
+/// how many projects and contexts get a bar
+const TOP: usize = 10;
+
+/// Stats are the figures behind the dashboard, worked out once when the view
+/// is built from both the todo and done files
+struct Stats {
+    completed: Vec<u32>,
+    created: Vec<u32>,
+    overdue: Vec<u32>,
+    weeks: Vec<(String, u64)>,
+    projects: Vec<(String, u64)>,
+    contexts: Vec<(String, u64)>,
+    open: usize,
+    done: usize,
+    average_age: Option<i64>,
+    start: NaiveDate,
+    today: NaiveDate,
+}
+
+impl Stats {
+    fn new(tasks: &[String], done: &[String], today: NaiveDate) -> Self {
+        let start = today - Duration::days(i64::from(DAYS - 1));
+        let monday = today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+        let day_index = |d: NaiveDate| {
+            usize::try_from((d - start).num_days())
+                .ok()
+                .filter(|&i| i < DAYS as usize)
+        };
+
+        let mut stats = Self {
+            completed: vec![0; DAYS as usize],
+            created: vec![0; DAYS as usize],
+            overdue: vec![0; DAYS as usize],
+            weeks: (0..WEEKS)
+                .rev()
+                .map(|w| {
+                    let week = monday - Duration::weeks(i64::from(w));
+                    (format!("W{}", week.iso_week().week()), 0)
+                })
+                .collect(),
+            projects: Vec::new(),
+            contexts: Vec::new(),
+            open: 0,
+            done: 0,
+            average_age: None,
+            start,
+            today,
+        };
+
+        let mut projects: HashMap<&str, u64> = HashMap::new();
+        let mut contexts: HashMap<&str, u64> = HashMap::new();
+        let mut ages = Vec::new();
+
+        for (i, line) in tasks.iter().chain(done).enumerate() {
+            let item = ParsedLine::new(line, i);
+            let created = item.start_date.and_then(date::from_iso);
+            let completed = item.completion_date.and_then(date::from_iso);
+            let due = item.due_date.and_then(date::from_iso);
+
+            if let Some(d) = created.and_then(day_index) {
+                stats.created[d] += 1;
+            }
+            if let Some(c) = completed {
+                if let Some(d) = day_index(c) {
+                    stats.completed[d] += 1;
+                }
+                // rounded up, so the days after this Monday are this week
+                let weeks_ago = ((monday - c).num_days() + 6).div_euclid(7);
+                if let Some(w) = u32::try_from(weeks_ago).ok().filter(|&w| w < WEEKS) {
+                    stats.weeks[(WEEKS - 1 - w) as usize].1 += 1;
+                }
+            }
+
+            if item.complete {
+                stats.done += 1;
+            } else {
+                stats.open += 1;
+                ages.extend(created.map(|c| (today - c).num_days()));
+                for project in &item.tags {
+                    *projects.entry(project).or_default() += 1;
+                }
+                for context in &item.contexts {
+                    *contexts.entry(context).or_default() += 1;
+                }
+            }
+
+            if let Some(due) = due {
+                let mut day = start;
+                for count in &mut stats.overdue {
+                    let open =
+                        created.is_none_or(|c| c <= day) && completed.is_none_or(|c| c > day);
+                    if open && due < day {
+                        *count += 1;
+                    }
+                    day = day.succ();
+                }
+            }
+        }
+
+        stats.projects = top(projects);
+        stats.contexts = top(contexts);
+        stats.average_age = i64::try_from(ages.len())
+            .ok()
+            .filter(|&n| n > 0)
+            .map(|n| ages.iter().sum::<i64>() / n);
+        stats
+    }
+}
+
+/// `top` orders the counts from most to least, keeping the first few
+fn top(counts: HashMap<&str, u64>) -> Vec<(String, u64)> {
+    let mut counts: Vec<(String, u64)> = counts
+        .into_iter()
+        .map(|(name, count)| (name.to_owned(), count))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(TOP);
+    counts
+}
+
+fn widen(counts: &[u32]) -> Vec<u64> {
+    counts.iter().map(|&c| u64::from(c)).collect()
+}
+
+fn points(counts: &[u32]) -> Vec<(f64, f64)> {
+    (0..DAYS)
+        .zip(counts)
+        .map(|(x, &y)| (f64::from(x), f64::from(y)))
+        .collect()
+}
+
+fn titled(title: &str) -> Block<'_> {
+    Block::default().borders(Borders::ALL).title(title)
+}
+
+pub struct StatsView<'a> {
+    status: &'a mut Option<String>,
+    stats: Stats,
+}
+
+impl<'a> StatsView<'a> {
+    pub fn new(
+        status: &'a mut Option<String>,
+        tasks: &[String],
+        done: &[String],
+        today: NaiveDate,
+    ) -> Self {
+        Self {
+            status,
+            stats: Stats::new(tasks, done, today),
+        }
+    }
+
+    fn draw_summary<B: Backend>(&self, f: &mut Frame<'_, B>, chunk: Rect) {
+        let stats = &self.stats;
+        let dim = Style::default().fg(Color::DarkGray);
+        let overdue = stats.overdue.last().copied().unwrap_or(0);
+        let age = stats
+            .average_age
+            .map_or_else(|| "-".to_owned(), |a| format!("{}d", a));
+
+        let line = Spans::from(vec![
+            Span::styled("open ", dim),
+            Span::raw(stats.open.to_string()),
+            Span::styled("  done ", dim),
+            Span::raw(stats.done.to_string()),
+            Span::styled("  average age of open tasks ", dim),
+            Span::raw(age),
+            Span::styled("  overdue ", dim),
+            Span::styled(
+                overdue.to_string(),
+                Style::default().fg(if overdue > 0 {
+                    Color::Red
+                } else {
+                    Color::Green
+                }),
+            ),
+        ]);
+        f.render_widget(Paragraph::new(line).block(titled("Stats")), chunk);
+    }
+
+    fn draw_created_vs_completed<B: Backend>(&self, f: &mut Frame<'_, B>, chunk: Rect) {
+        let stats = &self.stats;
+        let created = points(&stats.created);
+        let completed = points(&stats.completed);
+        let max = stats
+            .created
+            .iter()
+            .chain(&stats.completed)
+            .max()
+            .copied()
+            .unwrap_or(0)
+            + 1;
+
+        let datasets = vec![
+            Dataset::default()
+                .name("created")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Yellow))
+                .data(&created),
+            Dataset::default()
+                .name("completed")
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Green))
+                .data(&completed),
+        ];
+
+        let chart = Chart::new(datasets)
+            .block(titled("Created vs completed"))
+            .x_axis(
+                Axis::default()
+                    .style(Style::default().fg(Color::DarkGray))
+                    .bounds([0.0, f64::from(DAYS - 1)])
+                    .labels(vec![
+                        Span::raw(stats.start.format("%-d %b").to_string()),
+                        Span::raw(stats.today.format("%-d %b").to_string()),
+                    ]),
+            )
+            .y_axis(
+                Axis::default()
+                    .style(Style::default().fg(Color::DarkGray))
+                    .bounds([0.0, f64::from(max)])
+                    .labels(vec![Span::raw("0"), Span::raw(max.to_string())]),
+            );
+        f.render_widget(chart, chunk);
+    }
+
+    fn draw_open_by<B: Backend>(
+        f: &mut Frame<'_, B>,
+        chunk: Rect,
+        title: &str,
+        counts: &[(String, u64)],
+        color: Color,
+    ) {
+        let data: Vec<(&str, u64)> = counts.iter().map(|(n, c)| (&n[..], *c)).collect();
+        let chart = BarChart::default()
+            .block(titled(title))
+            .data(&data)
+            .bar_width(8)
+            .bar_style(Style::default().fg(color))
+            .value_style(Style::default().fg(Color::Black).bg(color));
+        f.render_widget(chart, chunk);
+    }
+}
+
+impl<'a> View for StatsView<'a> {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([Length(3), Percentage(30), Percentage(30), Min(0), Length(1)].as_ref())
+            .split(f.size());
+        let halves = |row: Rect| {
+            Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Percentage(50), Percentage(50)].as_ref())
+                .split(row)
+        };
+
+        self.draw_summary(f, rows[0]);
+
+        let stats = &self.stats;
+        let panes = halves(rows[1]);
+        let completed = widen(&stats.completed);
+        f.render_widget(
+            Sparkline::default()
+                .block(titled("Completed per day"))
+                .style(Style::default().fg(Color::Green))
+                .data(&completed),
+            panes[0],
+        );
+        let weeks: Vec<(&str, u64)> = stats.weeks.iter().map(|(w, c)| (&w[..], *c)).collect();
+        f.render_widget(
+            BarChart::default()
+                .block(titled("Completed per week"))
+                .data(&weeks)
+                .bar_width(4)
+                .bar_style(Style::default().fg(Color::Green))
+                .value_style(Style::default().fg(Color::Black).bg(Color::Green)),
+            panes[1],
+        );
+
+        let panes = halves(rows[2]);
+        self.draw_created_vs_completed(f, panes[0]);
+        let overdue = widen(&stats.overdue);
+        f.render_widget(
+            Sparkline::default()
+                .block(titled("Overdue"))
+                .style(Style::default().fg(Color::Red))
+                .data(&overdue),
+            panes[1],
+        );
+
+        let panes = halves(rows[3]);
+        Self::draw_open_by(f, panes[0], "Open by project", &stats.projects, Color::Cyan);
+        Self::draw_open_by(
+            f,
+            panes[1],
+            "Open by context",
+            &stats.contexts,
+            Color::Magenta,
+        );
+
+        let status_line = self.status.as_ref().map_or_else(Spans::default, |s| {
+            Spans::from(Span::styled(&s[..], Style::default().fg(Color::Yellow)))
+        });
+        f.render_widget(Paragraph::new(status_line), rows[4]);
+    }
+}
+
+impl<'a> EventHandler<Key> for StatsView<'a> {
+    fn handle(&mut self, event: Event<Key>) -> Option<Action> {
+        let Event::Input(key) = event else {
+            return None;
+        };
+
+        *self.status = None;
+        match key {
+            Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => Some(Action::Exit),
+            Key::Char('r') => Some(Action::Reload),
+            Key::Char('S') | Key::Esc => Some(Action::SwitchView(ViewKind::Main)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|&l| l.to_owned()).collect()
+    }
+
+    #[test]
+    fn counts_both_files() {
+        // a Wednesday, so the series start on 2020-09-15
+        let today = NaiveDate::from_ymd_opt(2020, 10, 14).unwrap();
+        let tasks = lines(&[
+            "2020-10-10 call bob +work @phone due:2020-10-12",
+            "2020-10-01 write report +work",
+        ]);
+        let done = lines(&[
+            "x 2020-10-14 2020-10-13 read +home",
+            "x 2020-10-11 2020-10-01 file taxes",
+        ]);
+        let stats = Stats::new(&tasks, &done, today);
+
+        assert_eq!((stats.open, stats.done), (2, 2));
+        assert_eq!(stats.created[16], 2);
+        assert_eq!(stats.created[25], 1);
+        assert_eq!(stats.created[28], 1);
+        assert_eq!(stats.completed[26], 1);
+        assert_eq!(stats.completed[29], 1);
+        assert_eq!(stats.overdue[27..], [0, 1, 1]);
+        assert_eq!(
+            stats.weeks[WEEKS as usize - 2..],
+            [("W41".to_owned(), 1), ("W42".to_owned(), 1)]
+        );
+        // only open tasks count towards these
+        assert_eq!(stats.projects, [("+work".to_owned(), 2)]);
+        assert_eq!(stats.contexts, [("@phone".to_owned(), 1)]);
+        assert_eq!(stats.average_age, Some(8));
+    }
+}
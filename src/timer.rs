@@ -0,0 +1,283 @@
+use crate::{config, error::Error, todo::ParsedLine};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use std::io::{prelude::*, BufReader};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    path::Path,
+};
+
+const TIMER_FILE: &str = "timer";
+const LOG_FILE: &str = "timelog";
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Timer is the clock running against a task. The task is found again by its
+/// `id:` when the timer stops, so the line is free to change in between.
+///
+/// A running timer is kept in a file in the state directory, so it survives
+/// the program exiting or crashing.
+#[derive(Debug, Clone)]
+pub struct Timer {
+    pub id: String,
+    pub body: String,
+    pub started: NaiveDateTime,
+}
+
+impl Timer {
+    pub fn new(id: String, body: String, started: NaiveDateTime) -> Self {
+        Self { id, body, started }
+    }
+
+    pub fn elapsed(&self, now: NaiveDateTime) -> Duration {
+        now - self.started
+    }
+
+    /// `describe` is the running timer as shown in the status bar
+    pub fn describe(&self, now: NaiveDateTime) -> String {
+        let secs = self.elapsed(now).num_seconds().max(0);
+        format!(
+            "⏱ {}:{:02}:{:02} {}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            self.body
+        )
+    }
+
+    /// `load` reads back the running timer, if there is one
+    pub fn load() -> Result<Option<Timer>, Error> {
+        let path = config::state_dir().join(TIMER_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&path).map_err(Error::io(&path))?;
+        let things: Vec<&str> = contents.trim_end().splitn(3, ' ').collect();
+        let timer = match things[..] {
+            [started, id, body] => NaiveDateTime::parse_from_str(started, TIME_FORMAT)
+                .ok()
+                .map(|started| Timer::new(id.to_owned(), body.to_owned(), started)),
+            _ => None,
+        };
+
+        timer.map(Some).ok_or_else(|| Error::Parse {
+            path,
+            line: 1,
+            message: "malformed timer".to_owned(),
+        })
+    }
+
+    /// `save` records the timer as running. The file is written in full
+    /// before being moved into place so a crash can't leave half of it.
+    pub fn save(&self) -> Result<(), Error> {
+        let dir = config::state_dir();
+        fs::create_dir_all(&dir).map_err(Error::io(&dir))?;
+
+        let tmp = dir.join(format!("{}.tmp", TIMER_FILE));
+        let mut f = File::create(&tmp).map_err(Error::io(&tmp))?;
+        writeln!(
+            f,
+            "{} {} {}",
+            self.started.format(TIME_FORMAT),
+            self.id,
+            self.body
+        )
+        .and_then(|()| f.sync_all())
+        .map_err(Error::io(&tmp))?;
+        let path = dir.join(TIMER_FILE);
+        fs::rename(&tmp, &path).map_err(Error::io(&path))
+    }
+
+    /// `clear` records that no timer is running
+    pub fn clear() -> Result<(), Error> {
+        let path = config::state_dir().join(TIMER_FILE);
+        if path.exists() {
+            fs::remove_file(&path).map_err(Error::io(&path))?;
+        }
+        Ok(())
+    }
+
+    /// `log` appends the stopped timer to the time log along with the task
+    /// line, which the report takes projects and contexts from
+    pub fn log(&self, stopped: NaiveDateTime, raw: &str) -> Result<(), Error> {
+        let dir = config::state_dir();
+        fs::create_dir_all(&dir).map_err(Error::io(&dir))?;
+
+        let path = dir.join(LOG_FILE);
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(Error::io(&path))?;
+        writeln!(
+            f,
+            "{} {} {}",
+            self.started.format(TIME_FORMAT),
+            stopped.format(TIME_FORMAT),
+            raw
+        )
+        .map_err(Error::io(&path))
+    }
+}
+
+/// `parse_spent` reads a `spent:` value such as `1h25m`, `2h` or `40m` as a
+/// number of minutes
+pub fn parse_spent(value: &str) -> Option<i64> {
+    let mut total = 0;
+    let mut digits = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            'h' | 'm' => {
+                let amount: i64 = digits.parse().ok()?;
+                total += if c == 'h' { amount * 60 } else { amount };
+                digits.clear();
+            }
+            _ => return None,
+        }
+    }
+    if digits.is_empty() && !value.is_empty() {
+        Some(total)
+    } else {
+        None
+    }
+}
+
+/// `format_spent` writes a number of minutes the way `spent:` holds them
+pub fn format_spent(minutes: i64) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, 0) => format!("{}h", h),
+        (h, m) => format!("{}h{}m", h, m),
+    }
+}
+
+/// `report` totals the logged time started between `from` and `to`
+/// inclusive, by project and by context
+pub fn report(from: NaiveDate, to: NaiveDate) -> Result<String, Error> {
+    report_from(&config::state_dir().join(LOG_FILE), from, to)
+}
+
+/// `report_from` is `report` over the time log at `path`
+fn report_from(path: &Path, from: NaiveDate, to: NaiveDate) -> Result<String, Error> {
+    let mut projects: BTreeMap<String, i64> = BTreeMap::new();
+    let mut contexts: BTreeMap<String, i64> = BTreeMap::new();
+    let mut total = 0;
+
+    if path.exists() {
+        let file = File::open(path).map_err(Error::io(path))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(Error::io(path))?;
+            let things: Vec<&str> = line.splitn(3, ' ').collect();
+            let [started, stopped, raw] = things[..] else {
+                continue;
+            };
+            let (Ok(started), Ok(stopped)) = (
+                NaiveDateTime::parse_from_str(started, TIME_FORMAT),
+                NaiveDateTime::parse_from_str(stopped, TIME_FORMAT),
+            ) else {
+                continue;
+            };
+            if started.date() < from || started.date() > to {
+                continue;
+            }
+
+            let minutes = (stopped - started).num_minutes();
+            let item = ParsedLine::new(raw, 0);
+            add_time(&mut projects, &item.tags, minutes);
+            add_time(&mut contexts, &item.contexts, minutes);
+            total += minutes;
+        }
+    }
+
+    let mut lines = vec![format!("Time from {} to {}", from, to)];
+    for (title, totals) in &[("Projects", projects), ("Contexts", contexts)] {
+        lines.push(String::new());
+        lines.push((*title).to_owned());
+        for (name, minutes) in totals {
+            lines.push(format!("  {:<24} {:>8}", name, format_spent(*minutes)));
+        }
+    }
+    lines.push(String::new());
+    lines.push(format!("  {:<24} {:>8}", "Total", format_spent(total)));
+    Ok(lines.join("\n"))
+}
+
+/// `add_time` adds `minutes` to each of `names`, or to `(none)` if the task
+/// has none
+fn add_time(totals: &mut BTreeMap<String, i64>, names: &[&str], minutes: i64) {
+    if names.is_empty() {
+        *totals.entry("(none)".to_owned()).or_default() += minutes;
+    }
+    for name in names {
+        *totals.entry((*name).to_owned()).or_default() += minutes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn spent_round_trips() {
+        for &(minutes, spent) in &[
+            (0, "0m"),
+            (40, "40m"),
+            (60, "1h"),
+            (90, "1h30m"),
+            (125, "2h5m"),
+        ] {
+            assert_eq!(format_spent(minutes), spent);
+            assert_eq!(parse_spent(spent), Some(minutes));
+        }
+        assert_eq!(parse_spent("1h90m"), Some(150));
+    }
+
+    #[test]
+    fn spent_rejects_the_rest() {
+        for &spent in &["", "x", "-5m", "5", "1h5", "h", "1.5h"] {
+            assert_eq!(parse_spent(spent), None, "{}", spent);
+        }
+    }
+
+    #[test]
+    fn report_includes_both_ends() {
+        let path = std::env::temp_dir().join(format!("todotui-rs-{}-timelog", process::id()));
+        fs::write(
+            &path,
+            "2020-10-11T23:00:00 2020-10-12T01:00:00 before +work\n\
+             2020-10-12T09:00:00 2020-10-12T10:30:00 call bob +work @phone\n\
+             not a log line\n\
+             2020-10-14T23:50:00 2020-10-15T00:10:00 read\n\
+             2020-10-15T09:00:00 2020-10-15T10:00:00 after +work\n",
+        )
+        .unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2020, 10, d).unwrap();
+
+        // the columns are padded, so compared with the padding squeezed out
+        let lines: Vec<String> = report_from(&path, day(12), day(14))
+            .unwrap()
+            .lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "Time from 2020-10-12 to 2020-10-14",
+                "",
+                "Projects",
+                "(none) 20m",
+                "+work 1h30m",
+                "",
+                "Contexts",
+                "(none) 20m",
+                "@phone 1h30m",
+                "",
+                "Total 1h50m",
+            ]
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
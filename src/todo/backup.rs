@@ -0,0 +1,156 @@
+use crate::error::Error;
+
+use chrono::{Local, NaiveDateTime};
+use log::{debug, info};
+use std::{
+    cmp::Reverse,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// the time a backup was taken, appended to the name of the file
+const STAMP: &str = "%Y%m%d-%H%M%S%.3f";
+
+/// `Backups` are copies of files taken just before they're replaced, kept in
+/// one directory and named after the whole path of the file and when they
+/// were taken. Only the latest few of each file are kept.
+#[derive(Debug, Clone)]
+pub struct Backups {
+    dir: PathBuf,
+    keep: usize,
+}
+
+/// `Backup` is one copy of a file
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub taken: NaiveDateTime,
+}
+
+impl Backups {
+    /// `new` keeps `keep` backups of each file in `dir`, none if it's 0
+    #[must_use]
+    pub fn new(dir: PathBuf, keep: usize) -> Self {
+        Self { dir, keep }
+    }
+
+    /// `save` copies the file at `path`, if there is one, then removes its
+    /// oldest backups beyond the number kept
+    ///
+    /// # Errors
+    ///
+    /// Fails if the backup directory can't be made or the file copied.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if self.keep == 0 || !path.exists() {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir).map_err(Error::io(&self.dir))?;
+        let backup = self
+            .dir
+            .join(format!("{}.{}", key(path), Local::now().format(STAMP)));
+        fs::copy(path, &backup).map_err(Error::io(&backup))?;
+        debug!("backed up {} to {}", path.display(), backup.display());
+
+        for old in self.list(path).into_iter().skip(self.keep) {
+            info!("removing the old backup {}", old.path.display());
+            fs::remove_file(&old.path).map_err(Error::io(&old.path))?;
+        }
+        Ok(())
+    }
+
+    /// `list` is every backup of the file at `path`, newest first
+    #[must_use]
+    pub fn list(&self, path: &Path) -> Vec<Backup> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let prefix = format!("{}.", key(path));
+
+        let mut backups: Vec<Backup> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter_map(|path| {
+                let name = path.file_name()?.to_str()?;
+                let stamp = name.strip_prefix(&prefix[..])?;
+                let taken = NaiveDateTime::parse_from_str(stamp, STAMP).ok()?;
+                Some(Backup { path, taken })
+            })
+            .collect();
+        backups.sort_by_key(|b| Reverse(b.taken));
+        backups
+    }
+}
+
+/// `key` is the whole path of `path` with `%` in place of each `/`, so files
+/// of the same name in different directories are told apart
+#[must_use]
+pub fn key(path: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    path.to_string_lossy().replace('/', "%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{process, thread, time::Duration};
+
+    /// `scratch` is an empty directory of its own for each test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("todotui-rs-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// `save_each` writes each of `contents` to `path` and backs it up, far
+    /// enough apart that every backup has its own stamp
+    fn save_each(backups: &Backups, path: &Path, contents: &[&str]) {
+        for content in contents {
+            fs::write(path, content).unwrap();
+            backups.save(path).unwrap();
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn keeps_the_newest() {
+        let dir = scratch("rotation");
+        let path = dir.join("todo.txt");
+        let backups = Backups::new(dir.join("backups"), 2);
+        save_each(&backups, &path, &["1", "2", "3"]);
+
+        let kept: Vec<String> = backups
+            .list(&path)
+            .iter()
+            .map(|b| fs::read_to_string(&b.path).unwrap())
+            .collect();
+        assert_eq!(kept, ["3", "2"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn files_of_the_same_name_are_kept_apart() {
+        let dir = scratch("apart");
+        fs::create_dir_all(dir.join("a")).unwrap();
+        fs::create_dir_all(dir.join("b")).unwrap();
+        let backups = Backups::new(dir.join("backups"), 1);
+        save_each(&backups, &dir.join("a/todo.txt"), &["a"]);
+        save_each(&backups, &dir.join("b/todo.txt"), &["b1", "b2"]);
+
+        let a = backups.list(&dir.join("a/todo.txt"));
+        assert_eq!(a.len(), 1);
+        assert_eq!(fs::read_to_string(&a[0].path).unwrap(), "a");
+        assert_eq!(backups.list(&dir.join("b/todo.txt")).len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn nothing_to_keep() {
+        let dir = scratch("none");
+        let path = dir.join("todo.txt");
+        Backups::new(dir.join("backups"), 3).save(&path).unwrap();
+        save_each(&Backups::new(dir.join("backups"), 0), &path, &["1"]);
+        assert!(Backups::new(dir.join("backups"), 3).list(&path).is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
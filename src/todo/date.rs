@@ -0,0 +1,244 @@
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, Weekday};
+use std::convert::TryFrom;
+
+pub const FORMAT: &str = "%Y-%m-%d";
+
+/// Clock is the source of "today" for anything that resolves relative dates.
+/// A `NaiveDate` is itself a clock frozen at the start of that day, which
+/// makes it easy to pin the date when needed.
+pub trait Clock {
+    fn now(&self) -> NaiveDateTime;
+
+    fn today(&self) -> NaiveDate {
+        self.now().date()
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+}
+
+impl Clock for NaiveDate {
+    fn now(&self) -> NaiveDateTime {
+        self.and_hms(0, 0, 0)
+    }
+}
+
+/// `parse` resolves a date expression relative to the clock.
+///
+/// Accepted forms are ISO dates (`2020-10-18`), `today`, `tomorrow`,
+/// `yesterday`, weekday names (`fri`, `friday`, `next-fri`) which resolve to
+/// the next such day after today, offsets (`+3d`, `-1w`, `2m`, `+1y`),
+/// `next-week`, `next-month`, `next-year` and the period ends `eow`, `eom`
+/// and `eoy`.
+pub fn parse(expr: &str, clock: &dyn Clock) -> Option<NaiveDate> {
+    let expr = expr.trim().to_lowercase();
+    if let Ok(date) = NaiveDate::parse_from_str(&expr, FORMAT) {
+        return Some(date);
+    }
+
+    let today = clock.today();
+    match &expr[..] {
+        "today" | "tod" => return Some(today),
+        "tomorrow" | "tom" => return Some(today + Duration::days(1)),
+        "yesterday" => return Some(today - Duration::days(1)),
+        "next-week" => return Some(next_weekday(today, Weekday::Mon)),
+        "next-month" => return add_months(first_of_month(today), 1),
+        "next-year" => return NaiveDate::from_ymd_opt(today.year() + 1, 1, 1),
+        "eow" => return Some(today + Duration::days(6 - days_from_monday(today))),
+        "eom" => return add_months(first_of_month(today), 1).map(|d| d - Duration::days(1)),
+        "eoy" => return NaiveDate::from_ymd_opt(today.year(), 12, 31),
+        _ => {}
+    }
+
+    if let Some(weekday) = parse_weekday(expr.trim_start_matches("next-")) {
+        return Some(next_weekday(today, weekday));
+    }
+
+    offset(today, &expr)
+}
+
+/// `shift` resolves `expr` like `parse`, except that signed offsets such as
+/// `+1w` are applied to `base` rather than to today
+pub fn shift(expr: &str, base: NaiveDate, clock: &dyn Clock) -> Option<NaiveDate> {
+    let expr = expr.trim();
+    if expr.starts_with('+') || expr.starts_with('-') {
+        return offset(base, expr);
+    }
+    parse(expr, clock)
+}
+
+fn offset(today: NaiveDate, expr: &str) -> Option<NaiveDate> {
+    let (negative, expr) = match expr.chars().next()? {
+        '+' => (false, &expr[1..]),
+        '-' => (true, &expr[1..]),
+        _ => (false, expr),
+    };
+
+    let unit = expr.chars().last()?;
+    let amount: i64 = expr[..expr.len() - unit.len_utf8()].parse().ok()?;
+    let amount = if negative { -amount } else { amount };
+
+    match unit {
+        'd' => today.checked_add_signed(Duration::days(amount)),
+        'w' => today.checked_add_signed(Duration::weeks(amount)),
+        'm' => add_months(today, amount),
+        'y' => add_months(today, amount * 12),
+        _ => None,
+    }
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    let weekday = match word {
+        "mon" | "monday" => Weekday::Mon,
+        "tue" | "tues" | "tuesday" => Weekday::Tue,
+        "wed" | "wednesday" => Weekday::Wed,
+        "thu" | "thur" | "thurs" | "thursday" => Weekday::Thu,
+        "fri" | "friday" => Weekday::Fri,
+        "sat" | "saturday" => Weekday::Sat,
+        "sun" | "sunday" => Weekday::Sun,
+        _ => return None,
+    };
+    Some(weekday)
+}
+
+fn days_from_monday(date: NaiveDate) -> i64 {
+    i64::from(date.weekday().num_days_from_monday())
+}
+
+/// `from_iso` parses a date stored on a task line
+#[must_use]
+pub fn from_iso(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, FORMAT).ok()
+}
+
+/// `span` describes a number of days compactly, e.g. `5d`, `3w`, `2mo`, `1y`
+#[must_use]
+pub fn span(days: i64) -> String {
+    let days = days.abs();
+    match days {
+        0..=13 => format!("{}d", days),
+        14..=59 => format!("{}w", days / 7),
+        60..=364 => format!("{}mo", days / 30),
+        _ => format!("{}y", days / 365),
+    }
+}
+
+/// `next_weekday` returns the first `weekday` strictly after `date`
+#[must_use]
+pub fn next_weekday(date: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let diff = i64::from(weekday.num_days_from_monday()) - days_from_monday(date);
+    date + Duration::days(if diff > 0 { diff } else { diff + 7 })
+}
+
+#[must_use]
+pub fn first_of_month(date: NaiveDate) -> NaiveDate {
+    NaiveDate::from_ymd(date.year(), date.month(), 1)
+}
+
+/// `add_months` moves `date` by whole months, clamping the day to the length of
+/// the target month
+#[must_use]
+pub fn add_months(date: NaiveDate, months: i64) -> Option<NaiveDate> {
+    let total = i64::from(date.year()) * 12 + i64::from(date.month0()) + months;
+    let year = i32::try_from(total.div_euclid(12)).ok()?;
+    let month = u32::try_from(total.rem_euclid(12)).ok()? + 1;
+
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a Wednesday
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd(2020, 10, 14)
+    }
+
+    fn ymd(y: i32, m: u32, d: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(y, m, d)
+    }
+
+    #[test]
+    fn parse_named_days() {
+        assert_eq!(parse("today", &today()), ymd(2020, 10, 14));
+        assert_eq!(parse("tom", &today()), ymd(2020, 10, 15));
+        assert_eq!(parse("yesterday", &today()), ymd(2020, 10, 13));
+        assert_eq!(parse(" Tomorrow ", &today()), ymd(2020, 10, 15));
+        assert_eq!(parse("2020-12-25", &today()), ymd(2020, 12, 25));
+    }
+
+    #[test]
+    fn parse_weekdays_after_today() {
+        assert_eq!(parse("fri", &today()), ymd(2020, 10, 16));
+        assert_eq!(parse("next-friday", &today()), ymd(2020, 10, 16));
+        // today's weekday is a week away
+        assert_eq!(parse("wed", &today()), ymd(2020, 10, 21));
+        assert_eq!(parse("next-week", &today()), ymd(2020, 10, 19));
+    }
+
+    #[test]
+    fn parse_period_ends() {
+        assert_eq!(parse("eow", &today()), ymd(2020, 10, 18));
+        assert_eq!(parse("eom", &today()), ymd(2020, 10, 31));
+        assert_eq!(parse("eoy", &today()), ymd(2020, 12, 31));
+        assert_eq!(parse("next-month", &today()), ymd(2020, 11, 1));
+        assert_eq!(parse("next-year", &today()), ymd(2021, 1, 1));
+    }
+
+    #[test]
+    fn parse_offsets() {
+        assert_eq!(parse("+3d", &today()), ymd(2020, 10, 17));
+        assert_eq!(parse("-1w", &today()), ymd(2020, 10, 7));
+        assert_eq!(parse("2m", &today()), ymd(2020, 12, 14));
+        assert_eq!(parse("+1y", &today()), ymd(2021, 10, 14));
+    }
+
+    #[test]
+    fn parse_rejects_the_rest() {
+        for expr in &["", "someday", "+d", "+3x", "3é", "2020-13-01"] {
+            assert_eq!(parse(expr, &today()), None, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn offset_signs() {
+        assert_eq!(offset(today(), "10d"), ymd(2020, 10, 24));
+        assert_eq!(offset(today(), "-10d"), ymd(2020, 10, 4));
+        assert_eq!(offset(today(), "-"), None);
+    }
+
+    #[test]
+    fn shift_moves_from_base() {
+        let base = NaiveDate::from_ymd(2020, 11, 1);
+        assert_eq!(shift("+1w", base, &today()), ymd(2020, 11, 8));
+        assert_eq!(shift("-2d", base, &today()), ymd(2020, 10, 30));
+        // anything else is still relative to today
+        assert_eq!(shift("fri", base, &today()), ymd(2020, 10, 16));
+    }
+
+    #[test]
+    fn add_months_clamps_the_day() {
+        let date = |y, m, d| NaiveDate::from_ymd(y, m, d);
+        assert_eq!(add_months(date(2020, 1, 31), 1), ymd(2020, 2, 29));
+        assert_eq!(add_months(date(2021, 1, 31), 1), ymd(2021, 2, 28));
+        assert_eq!(add_months(date(2020, 3, 31), -1), ymd(2020, 2, 29));
+        assert_eq!(add_months(date(2020, 11, 15), 2), ymd(2021, 1, 15));
+        assert_eq!(add_months(date(2020, 1, 15), -1), ymd(2019, 12, 15));
+    }
+
+    #[test]
+    fn span_units() {
+        assert_eq!(span(5), "5d");
+        assert_eq!(span(-21), "3w");
+        assert_eq!(span(90), "3mo");
+        assert_eq!(span(800), "2y");
+    }
+}
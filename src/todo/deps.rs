@@ -0,0 +1,216 @@
+use super::{tree, ParsedLine};
+
+use std::collections::HashSet;
+
+/// Deps links tasks through their `dep:` extensions. It's built from the
+/// whole list rather than what's on screen, so a task stays blocked by a
+/// task that's filtered out, and everything is indexed by line in the file.
+/// Tasks already archived to the done file count as complete.
+pub struct Deps {
+    blockers: Vec<Vec<usize>>,
+    blocks: Vec<Vec<usize>>,
+    complete: Vec<bool>,
+    bodies: Vec<String>,
+    /// dangling references and cycles found while linking
+    pub warnings: Vec<String>,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Visit {
+    New,
+    Active,
+    Done,
+}
+
+impl Deps {
+    #[must_use]
+    pub fn new(items: &[ParsedLine], done: &[ParsedLine]) -> Self {
+        let ids = tree::ids(items);
+        let archived: HashSet<&str> = done.iter().filter_map(ParsedLine::id).collect();
+        let mut blockers = vec![Vec::new(); items.len()];
+        let mut blocks = vec![Vec::new(); items.len()];
+        let mut warnings = Vec::new();
+
+        for (i, item) in items.iter().enumerate() {
+            for dep in item.deps() {
+                match ids.get(dep) {
+                    Some(&b) => {
+                        blockers[i].push(b);
+                        blocks[b].push(i);
+                    }
+                    // done and archived, so it blocks nothing
+                    None if archived.contains(dep) => {}
+                    None => warnings.push(format!(
+                        "line {}: dep:{} matches no task",
+                        item.index + 1,
+                        dep
+                    )),
+                }
+            }
+        }
+
+        let mut deps = Self {
+            blockers,
+            blocks,
+            complete: items.iter().map(|item| item.complete).collect(),
+            bodies: items.iter().map(|item| item.body.clone()).collect(),
+            warnings,
+        };
+
+        let mut visits = vec![Visit::New; items.len()];
+        let mut path = Vec::new();
+        for i in 0..items.len() {
+            deps.find_cycles(items, i, &mut visits, &mut path);
+        }
+
+        deps
+    }
+
+    /// `find_cycles` walks the blockers of `i` depth first, reporting a
+    /// warning whenever the walk comes back around to a task on its `path`
+    fn find_cycles(
+        &mut self,
+        items: &[ParsedLine],
+        i: usize,
+        visits: &mut [Visit],
+        path: &mut Vec<usize>,
+    ) {
+        match visits[i] {
+            Visit::Done => return,
+            Visit::Active => {
+                let start = path.iter().position(|&p| p == i).unwrap_or(0);
+                let cycle: Vec<&str> = path[start..]
+                    .iter()
+                    .chain(Some(&i))
+                    .map(|&p| items[p].id().unwrap_or("?"))
+                    .collect();
+                self.warnings
+                    .push(format!("dependency cycle: {}", cycle.join(" → ")));
+                return;
+            }
+            Visit::New => {}
+        }
+
+        visits[i] = Visit::Active;
+        path.push(i);
+        for b in self.blockers[i].clone() {
+            self.find_cycles(items, b, visits, path);
+        }
+        path.pop();
+        visits[i] = Visit::Done;
+    }
+
+    /// `blockers` are the tasks `i` depends on
+    #[must_use]
+    pub fn blockers(&self, i: usize) -> &[usize] {
+        self.blockers.get(i).map_or(&[], |b| &b[..])
+    }
+
+    /// `blocks` are the tasks depending on `i`
+    #[must_use]
+    pub fn blocks(&self, i: usize) -> &[usize] {
+        self.blocks.get(i).map_or(&[], |b| &b[..])
+    }
+
+    /// `is_blocked` is true while any task `i` depends on is still open
+    #[must_use]
+    pub fn is_blocked(&self, i: usize) -> bool {
+        self.blockers(i).iter().any(|&b| !self.complete[b])
+    }
+
+    #[must_use]
+    pub fn is_complete(&self, i: usize) -> bool {
+        self.complete[i]
+    }
+
+    #[must_use]
+    pub fn body(&self, i: usize) -> &str {
+        &self.bodies[i]
+    }
+
+    /// `summary` is a one line description of the warnings, if there are any
+    #[must_use]
+    pub fn summary(&self) -> Option<String> {
+        match &self.warnings[..] {
+            [] => None,
+            [warning] => Some(warning.clone()),
+            [first, rest @ ..] => Some(format!(
+                "{} (+{} more dependency warnings)",
+                first,
+                rest.len()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse<'a>(lines: &[&'a str]) -> Vec<ParsedLine<'a>> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| ParsedLine::new(line, i))
+            .collect()
+    }
+
+    fn deps(lines: &[&str]) -> Deps {
+        Deps::new(&parse(lines), &[])
+    }
+
+    #[test]
+    fn links_both_ways() {
+        let deps = deps(&[
+            "book hotel id:1",
+            "x 2020-10-14 book train id:2",
+            "pack bag dep:1,2",
+        ]);
+        assert_eq!(deps.blockers(2), [0, 1]);
+        assert_eq!(deps.blocks(0), [2]);
+        assert_eq!(deps.blockers(9), [] as [usize; 0]);
+        assert!(deps.warnings.is_empty());
+    }
+
+    #[test]
+    fn blocked_until_every_dependency_is_done() {
+        let open = deps(&["book hotel id:1", "pack bag dep:1"]);
+        assert!(open.is_blocked(1));
+        assert!(!open.is_blocked(0));
+
+        let done = deps(&["x 2020-10-14 book hotel id:1", "pack bag dep:1"]);
+        assert!(!done.is_blocked(1));
+    }
+
+    #[test]
+    fn warns_about_dangling_deps() {
+        let deps = deps(&["pack bag dep:7"]);
+        assert_eq!(deps.warnings, ["line 1: dep:7 matches no task"]);
+        assert!(!deps.is_blocked(0));
+    }
+
+    #[test]
+    fn archived_deps_are_done() {
+        let done = parse(&["x 2020-10-14 book hotel id:1"]);
+        let deps = Deps::new(&parse(&["pack bag dep:1"]), &done);
+        assert!(deps.warnings.is_empty());
+        assert!(!deps.is_blocked(0));
+
+        let deps = Deps::new(&parse(&["book train id:2", "pack bag dep:1,2"]), &done);
+        assert_eq!(deps.blockers(1), [0]);
+        assert!(deps.is_blocked(1));
+    }
+
+    #[test]
+    fn warns_about_cycles() {
+        let deps = deps(&["a id:1 dep:2", "b id:2 dep:1", "c id:3 dep:3"]);
+        assert_eq!(
+            deps.warnings,
+            ["dependency cycle: 1 → 2 → 1", "dependency cycle: 3 → 3"]
+        );
+        assert_eq!(
+            deps.summary().as_deref(),
+            Some("dependency cycle: 1 → 2 → 1 (+1 more dependency warnings)")
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use super::date::{self, Clock};
+use super::ParsedLine;
+
+use chrono::{Duration, NaiveDate};
+
+/// `set_extension` returns `raw` with the `key:value` extension set to `value`,
+/// replacing the first existing occurrence of `key` or appending it otherwise
+#[must_use]
+pub fn set_extension(raw: &str, key: &str, value: &str) -> String {
+    let prefix = format!("{}:", key);
+    let mut replaced = false;
+
+    let mut words: Vec<String> = raw
+        .split(' ')
+        .map(|word| {
+            if !replaced && word.starts_with(&prefix) {
+                replaced = true;
+                format!("{}{}", prefix, value)
+            } else {
+                word.to_owned()
+            }
+        })
+        .collect();
+
+    if !replaced {
+        words.push(format!("{}{}", prefix, value));
+    }
+
+    words.join(" ")
+}
+
+/// `get_extension` returns the value of the first `key:value` extension in `raw`
+#[must_use]
+pub fn get_extension<'a>(raw: &'a str, key: &str) -> Option<&'a str> {
+    raw.split(' ').find_map(|word| {
+        let things: Vec<&str> = word.splitn(2, ':').collect();
+        match things[..] {
+            [k, v] if k == key => Some(v),
+            _ => None,
+        }
+    })
+}
+
+/// `remove_extension` returns `raw` with every `key:value` extension removed
+#[must_use]
+pub fn remove_extension(raw: &str, key: &str) -> String {
+    let prefix = format!("{}:", key);
+    raw.split(' ')
+        .filter(|word| !word.starts_with(&prefix))
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// `defer` moves the `t:` threshold of `raw` to the date described by `expr`.
+/// Signed offsets like `+1d` shift the current threshold, or today if the
+/// task has none. When `shift_due` is set an existing `due:` date is moved by
+/// the same number of days. Returns `None` if `expr` isn't a date.
+pub fn defer(raw: &str, expr: &str, shift_due: bool, clock: &dyn Clock) -> Option<String> {
+    let today = clock.today();
+    let current = get_extension(raw, "t")
+        .and_then(date::from_iso)
+        .unwrap_or(today);
+    let threshold = date::shift(expr, current, clock)?;
+
+    let mut line = set_extension(raw, "t", &threshold.format(date::FORMAT).to_string());
+
+    if shift_due {
+        if let Some(due) = get_extension(&line, "due").and_then(date::from_iso) {
+            let due = due + Duration::days((threshold - current).num_days());
+            line = set_extension(&line, "due", &due.format(date::FORMAT).to_string());
+        }
+    }
+
+    Some(line)
+}
+
+/// `toggle_complete` marks `raw` as done on `today`, or reopens it along with
+/// dropping its completion date if it's already done
+#[must_use]
+pub fn toggle_complete(raw: &str, today: NaiveDate) -> String {
+    if let Some(rest) = raw.strip_prefix("x ") {
+        let rest = rest.trim_start();
+        match rest.split(' ').next() {
+            Some(word) if ParsedLine::is_date(word) => rest[word.len()..].trim_start().to_owned(),
+            _ => rest.to_owned(),
+        }
+    } else {
+        format!("x {} {}", today.format(date::FORMAT), raw)
+    }
+}
+
+/// `set_priority` returns `raw` with its priority replaced, or removed when
+/// `priority` is `None`
+#[must_use]
+pub fn set_priority(raw: &str, priority: Option<char>) -> String {
+    let rest = match raw.split(' ').next() {
+        Some(word) if ParsedLine::is_priority(word).is_some() => raw[word.len()..].trim_start(),
+        _ => raw,
+    };
+
+    match priority {
+        Some(p) => format!("({}) {}", p, rest),
+        None => rest.to_owned(),
+    }
+}
+
+/// `retag` replaces the `from` context or project of `raw` with `to`, or
+/// strips it when `to` is `None`. A task already carrying `to` keeps only the
+/// one, so renaming onto an existing name merges the two. Returns `None` if
+/// `raw` doesn't mention `from`.
+#[must_use]
+pub fn retag(raw: &str, from: &str, to: Option<&str>) -> Option<String> {
+    if !raw.split(' ').any(|word| word == from) {
+        return None;
+    }
+
+    let mut seen = false;
+    let words: Vec<&str> = raw
+        .split(' ')
+        .filter_map(|word| {
+            let word = if word == from { to? } else { word };
+            if Some(word) == to {
+                if seen {
+                    return None;
+                }
+                seen = true;
+            }
+            Some(word)
+        })
+        .collect();
+
+    Some(words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a Wednesday
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd(2020, 10, 14)
+    }
+
+    #[test]
+    fn set_extension_replaces_the_first() {
+        assert_eq!(set_extension("a due:1 b", "due", "2"), "a due:2 b");
+        assert_eq!(set_extension("a due:1 due:3", "due", "2"), "a due:2 due:3");
+        assert_eq!(set_extension("a b", "due", "2"), "a b due:2");
+    }
+
+    #[test]
+    fn get_extension_matches_whole_keys() {
+        assert_eq!(get_extension("a id:3 b", "id"), Some("3"));
+        assert_eq!(get_extension("a url:http://x", "url"), Some("http://x"));
+        assert_eq!(get_extension("a tag:x", "t"), None);
+        assert_eq!(get_extension("a b", "id"), None);
+    }
+
+    #[test]
+    fn remove_extension_removes_every_one() {
+        assert_eq!(remove_extension("a t:1 b t:2", "t"), "a b");
+        assert_eq!(remove_extension("a tt:1", "t"), "a tt:1");
+    }
+
+    #[test]
+    fn defer_sets_the_threshold() {
+        assert_eq!(
+            defer("call bob", "+1d", false, &today()).as_deref(),
+            Some("call bob t:2020-10-15")
+        );
+        assert_eq!(
+            defer("call bob t:2020-10-01", "fri", false, &today()).as_deref(),
+            Some("call bob t:2020-10-16")
+        );
+        assert_eq!(defer("call bob", "someday", false, &today()), None);
+    }
+
+    #[test]
+    fn defer_shifts_from_the_threshold() {
+        assert_eq!(
+            defer("call bob t:2020-10-20", "+1w", false, &today()).as_deref(),
+            Some("call bob t:2020-10-27")
+        );
+    }
+
+    #[test]
+    fn defer_shifts_due_when_asked() {
+        let line = "call bob t:2020-10-20 due:2020-10-22";
+        assert_eq!(
+            defer(line, "+2d", true, &today()).as_deref(),
+            Some("call bob t:2020-10-22 due:2020-10-24")
+        );
+        assert_eq!(
+            defer(line, "+2d", false, &today()).as_deref(),
+            Some("call bob t:2020-10-22 due:2020-10-22")
+        );
+        // without a threshold the due date moves as far as today does
+        assert_eq!(
+            defer("call bob due:2020-10-20", "+3d", true, &today()).as_deref(),
+            Some("call bob due:2020-10-23 t:2020-10-17")
+        );
+    }
+
+    #[test]
+    fn toggle_complete_round_trips() {
+        let done = toggle_complete("(A) 2020-10-01 call bob", today());
+        assert_eq!(done, "x 2020-10-14 (A) 2020-10-01 call bob");
+        assert_eq!(toggle_complete(&done, today()), "(A) 2020-10-01 call bob");
+        assert_eq!(toggle_complete("x call bob", today()), "call bob");
+    }
+
+    #[test]
+    fn set_priority_replaces_or_removes() {
+        assert_eq!(set_priority("call bob", Some('B')), "(B) call bob");
+        assert_eq!(set_priority("(A) call bob", Some('B')), "(B) call bob");
+        assert_eq!(set_priority("(A) call bob", None), "call bob");
+        assert_eq!(set_priority("(AB) call bob", None), "(AB) call bob");
+    }
+
+    #[test]
+    fn retag_renames() {
+        assert_eq!(
+            retag("a +foo b", "+foo", Some("+bar")).as_deref(),
+            Some("a +bar b")
+        );
+        assert_eq!(
+            retag("a +foo", "+foo", Some("+éx")).as_deref(),
+            Some("a +éx")
+        );
+        assert_eq!(retag("a +food", "+foo", Some("+bar")), None);
+    }
+
+    #[test]
+    fn retag_merges_onto_an_existing_name() {
+        assert_eq!(
+            retag("a +foo b +bar", "+foo", Some("+bar")).as_deref(),
+            Some("a +bar b")
+        );
+    }
+
+    #[test]
+    fn retag_strips() {
+        assert_eq!(
+            retag("a @home b @home", "@home", None).as_deref(),
+            Some("a b")
+        );
+    }
+}
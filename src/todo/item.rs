@@ -1,3 +1,5 @@
+use super::date::{self, Clock};
+
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
@@ -40,6 +42,7 @@ impl Display for Parse {
 }
 
 impl<'a> ParsedLine<'a> {
+    #[must_use]
     pub fn new(raw: &'a str, index: usize) -> Self {
         let x: &[char] = &['\n', '\r'];
         let mut raw = raw.trim_end_matches(x);
@@ -88,7 +91,8 @@ impl<'a> ParsedLine<'a> {
         item
     }
 
-    fn is_priority(word: &'a str) -> Option<char> {
+    #[must_use]
+    pub fn is_priority(word: &str) -> Option<char> {
         if word.len() != 3 {
             return None;
         }
@@ -106,7 +110,8 @@ impl<'a> ParsedLine<'a> {
         priority
     }
 
-    fn is_date(word: &'a str) -> bool {
+    #[must_use]
+    pub fn is_date(word: &str) -> bool {
         if word.len() != 10 {
             return false;
         }
@@ -129,6 +134,62 @@ impl<'a> ParsedLine<'a> {
         true
     }
 
+    /// `extension` is the value of the first `key:value` extension on the task
+    #[must_use]
+    pub fn extension(&self, key: &str) -> Option<&'a str> {
+        self.extensions
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|&(_, v)| v)
+    }
+
+    /// `id` is the identifier other tasks use to refer to this one
+    #[must_use]
+    pub fn id(&self) -> Option<&'a str> {
+        self.extension("id")
+    }
+
+    /// `parent` is the id of the task this is a subtask of, given by either
+    /// `parent:` or its short form `p:`
+    #[must_use]
+    pub fn parent(&self) -> Option<&'a str> {
+        self.extension("parent").or_else(|| self.extension("p"))
+    }
+
+    /// `deps` are the ids of the tasks which must be done before this one,
+    /// from any number of `dep:` extensions each holding one or more comma
+    /// separated ids
+    #[must_use]
+    pub fn deps(&self) -> Vec<&'a str> {
+        self.extensions
+            .iter()
+            .filter(|(k, _)| *k == "dep")
+            .flat_map(|(_, v)| v.split(','))
+            .filter(|id| !id.is_empty())
+            .collect()
+    }
+
+    /// `expand_dates` rewrites relative `due:` and `t:` values in `raw`, such as
+    /// `due:fri` or `t:+3d`, into concrete dates. Values which are already
+    /// dates or which can't be parsed are left untouched.
+    pub fn expand_dates(raw: &str, clock: &dyn Clock) -> String {
+        raw.split(' ')
+            .map(|word| {
+                let things: Vec<&str> = word.splitn(2, ':').collect();
+                match things[..] {
+                    [key @ ("due" | "t"), val] if !ParsedLine::is_date(val) => {
+                        match date::parse(val, clock) {
+                            Some(d) => format!("{}:{}", key, d.format(date::FORMAT)),
+                            None => word.to_owned(),
+                        }
+                    }
+                    _ => word.to_owned(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
     fn process_body(&mut self, word: &'a str) {
         match word.chars().next() {
             Some('@') => self.contexts.push(word),
@@ -189,3 +250,25 @@ impl<'a> ParsedLine<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn expand_dates_resolves_due_and_threshold() {
+        let today = NaiveDate::from_ymd(2020, 10, 14);
+        assert_eq!(
+            ParsedLine::expand_dates("call bob due:fri t:+3d", &today),
+            "call bob due:2020-10-16 t:2020-10-17"
+        );
+    }
+
+    #[test]
+    fn expand_dates_leaves_the_rest() {
+        let today = NaiveDate::from_ymd(2020, 10, 14);
+        let line = "call bob due:2020-01-01 t:someday note:fri +fri";
+        assert_eq!(ParsedLine::expand_dates(line, &today), line);
+    }
+}
@@ -1,89 +1,526 @@
-use crate::filters::Filters;
+use crate::{error::Error, filters::Filters};
 
-use super::item::ParsedLine;
+use super::{backup::Backups, item::ParsedLine};
 
-use std::io::{prelude::*, Error};
-use std::{collections::BTreeSet, fs, io::BufReader};
-use std::{fs::File, path::Path};
+use log::{debug, info, warn};
+use std::io::{prelude::*, ErrorKind};
+use std::{collections::BTreeSet, fs, io::BufReader, process, thread, time::SystemTime};
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{BufWriter, SeekFrom},
+    os::unix::fs::{fchown, MetadataExt},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// how long to wait for another process to finish writing the files
+pub const LOCK_WAIT: Duration = Duration::from_secs(2);
+
+/// counts the temporary files made by this process, so each has its own name
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
 
 pub struct Rep {
     pub tasks: Vec<String>,
+    /// the archived tasks from the done file, which is optional
+    pub done: Vec<String>,
     pub filters: Filters<Vec<String>>,
     pub modified: bool,
+    pub done_modified: bool,
+    /// when the todo file was last changed on disk as of reading or writing
+    /// it, to tell whether something else has changed it since
+    read_at: Option<SystemTime>,
+}
+
+/// Snapshot is a copy of both files taken before a batch edit, so the whole
+/// batch can be undone at once
+pub struct Snapshot {
+    tasks: Vec<String>,
+    done: Vec<String>,
 }
 
 impl Rep {
-    pub fn new(handle: &Handle) -> Result<Rep, Error> {
+    /// `new` loads the tasks from `handle` and the archived tasks from
+    /// `done_handle`, if that file exists
+    ///
+    /// # Errors
+    ///
+    /// Fails if either file can't be read.
+    pub fn new(handle: &Handle, done_handle: &Handle) -> Result<Rep, Error> {
+        let read_at = handle.modified_time();
         let items = handle.get_lines()?;
-        let mut contexts = BTreeSet::new();
-        let mut tags = BTreeSet::new();
-
-        for (i, line) in items.iter().enumerate() {
-            let i = ParsedLine::new(&line, i);
-            for c in i.contexts {
-                contexts.insert(c);
-            }
-            for t in i.tags {
-                tags.insert(t);
-            }
-        }
+        let done = if done_handle.exists() {
+            done_handle.get_lines()?
+        } else {
+            Vec::new()
+        };
 
         let list = Rep {
-            filters: Filters::new(
-                contexts.into_iter().map(str::to_string).collect(),
-                tags.into_iter().map(str::to_string).collect(),
-            ),
+            filters: collect_filters(&items),
             tasks: items,
+            done,
             modified: false,
+            done_modified: false,
+            read_at,
         };
 
         Ok(list)
     }
+
+    /// `save` writes whichever of the files were modified. Unless `force` is
+    /// set, the todo file is only written if nothing else has changed it
+    /// since it was read.
+    ///
+    /// Both files are written under the lock on the todo file, so nothing
+    /// else following the lock can change it between checking and writing.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Conflict`] if the todo file was changed by
+    /// something else, with [`Error::Locked`] if the lock is held for too
+    /// long, or if either file can't be written.
+    pub fn save(&mut self, handle: &Handle, done_handle: &Handle, force: bool) -> Result<(), Error> {
+        handle.writable()?;
+        done_handle.writable()?;
+        let _lock = handle.lock(LOCK_WAIT)?;
+        if self.modified {
+            if !force && handle.modified_time() != self.read_at {
+                warn!("{} changed since it was read", handle.path.display());
+                return Err(Error::Conflict {
+                    path: handle.path.to_owned(),
+                });
+            }
+            handle.write(&self.tasks)?;
+            self.read_at = handle.modified_time();
+            self.modified = false;
+        }
+        if self.done_modified {
+            done_handle.write(&self.done)?;
+            self.done_modified = false;
+        }
+        Ok(())
+    }
+
+    /// `write_line` puts `line` in place of `original` in the todo file on
+    /// disk straight away, for changes which can't wait to be saved. Any
+    /// other changes are left to be written by `save`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `original` isn't in the file, which it won't be if it was
+    /// only just added, or if the file can't be locked, read or written.
+    pub fn write_line(&mut self, handle: &Handle, original: &str, line: &str) -> Result<(), Error> {
+        handle.writable()?;
+        let _lock = handle.lock(LOCK_WAIT)?;
+        let unchanged = handle.modified_time() == self.read_at;
+        let mut lines = handle.get_lines()?;
+        let i = lines.iter().position(|l| l == original).ok_or_else(|| {
+            format!(
+                "the task isn't in {} yet, write it first",
+                handle.path.display()
+            )
+        })?;
+        line.clone_into(&mut lines[i]);
+        handle.write(&lines)?;
+        // changes made by something else still have to be noticed by `save`
+        if unchanged {
+            self.read_at = handle.modified_time();
+        }
+        Ok(())
+    }
+
+    /// `refresh_filters` rebuilds the contexts and tags after lines were
+    /// rewritten in bulk
+    pub fn refresh_filters(&mut self) {
+        self.filters = collect_filters(&self.tasks);
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tasks: self.tasks.clone(),
+            done: self.done.clone(),
+        }
+    }
+
+    /// `restore` puts back the lines from `snapshot`, leaving both files to
+    /// be written again
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.tasks = snapshot.tasks;
+        self.done = snapshot.done;
+        self.modified = true;
+        self.done_modified = true;
+        self.refresh_filters();
+    }
+}
+
+fn collect_filters(items: &[String]) -> Filters<Vec<String>> {
+    let mut contexts = BTreeSet::new();
+    let mut tags = BTreeSet::new();
+
+    for (i, line) in items.iter().enumerate() {
+        let i = ParsedLine::new(&line, i);
+        for c in i.contexts {
+            contexts.insert(c);
+        }
+        for t in i.tags {
+            tags.insert(t);
+        }
+    }
+
+    Filters::new(
+        contexts.into_iter().map(str::to_string).collect(),
+        tags.into_iter().map(str::to_string).collect(),
+    )
 }
 
 pub struct Handle<'a> {
     path: &'a Path,
+    backups: Option<&'a Backups>,
+    /// why the file mustn't be written, if it mustn't
+    read_only: Option<String>,
+}
+
+/// `Lock` is the advisory lock on a todo file, held until it's dropped
+pub struct Lock {
+    file: File,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // closing the file releases it anyway
+        let _ = self.file.unlock();
+    }
 }
 
 impl<'a> Handle<'a> {
+    #[must_use]
     pub fn new(path: &'a Path) -> Self {
-        Self { path }
+        Self {
+            path,
+            backups: None,
+            read_only: None,
+        }
+    }
+
+    /// `read_only` makes every write fail for `reason`
+    #[must_use]
+    pub fn read_only(self, reason: &str) -> Self {
+        Self {
+            read_only: Some(reason.to_owned()),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.is_some()
+    }
+
+    fn writable(&self) -> Result<(), Error> {
+        match &self.read_only {
+            Some(reason) => Err(Error::ReadOnly {
+                path: self.path.to_owned(),
+                reason: reason.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// `lock_path` is a file locked on behalf of this one, which can't be
+    /// locked itself as each write replaces it. `kind` tells apart the locks
+    /// taken for different reasons.
+    fn lock_path(&self, kind: &str) -> PathBuf {
+        let name = self
+            .path
+            .file_name()
+            .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+        self.path.with_file_name(format!(".{}.{}", name, kind))
     }
 
+    /// `lock` takes the advisory lock on the file, to be held while it's read,
+    /// changed and written back. It waits up to `wait` for another process
+    /// to let go of it, and the process holding it is written to the lock
+    /// file to be named if it doesn't.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Locked`] if the lock is still held after `wait`,
+    /// or if the lock file can't be opened.
+    pub fn lock(&self, wait: Duration) -> Result<Lock, Error> {
+        self.lock_at(&self.lock_path("lock"), wait)
+    }
+
+    /// `hold` takes the lock marking the file as open for editing, without
+    /// waiting. It's held for as long as the file is open, apart from the
+    /// lock taken to write it, so it keeps out another editor but not the
+    /// commands which only lock the file while they change it.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::Locked`] if something else has the file open, or
+    /// if the lock file can't be opened.
+    pub fn hold(&self) -> Result<Lock, Error> {
+        self.lock_at(&self.lock_path("open.lock"), Duration::from_secs(0))
+    }
+
+    fn lock_at(&self, path: &Path, wait: Duration) -> Result<Lock, Error> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(Error::io(path))?;
+
+        let started = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) if started.elapsed() < wait => {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(TryLockError::WouldBlock) => {
+                    let mut holder = String::new();
+                    let _ = file.read_to_string(&mut holder);
+                    return Err(Error::Locked {
+                        path: self.path.to_owned(),
+                        pid: holder.trim().parse().ok(),
+                    });
+                }
+                Err(TryLockError::Error(e)) => return Err(Error::io(path)(e)),
+            }
+        }
+
+        file.set_len(0)
+            .and_then(|()| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| writeln!(file, "{}", process::id()))
+            .map_err(Error::io(path))?;
+        debug!("locked {}", path.display());
+        Ok(Lock { file })
+    }
+
+    /// `backed_up` makes each write first copy the file it replaces into
+    /// `backups`
+    #[must_use]
+    pub fn backed_up(self, backups: &'a Backups) -> Self {
+        Self {
+            backups: Some(backups),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// `modified_time` is when the file was last changed, if it exists
+    #[must_use]
+    pub fn modified_time(&self) -> Option<SystemTime> {
+        fs::metadata(self.path).and_then(|m| m.modified()).ok()
+    }
+
+    /// `get_lines` reads every line of the file
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be opened or read, or with [`Error::Parse`]
+    /// naming the line which isn't valid UTF-8.
     pub fn get_lines(&self) -> Result<Vec<String>, Error> {
-        let file = File::open(self.path)?;
+        let file = File::open(self.path).map_err(Error::io(self.path))?;
         let buf_reader = BufReader::new(file);
 
         let mut lines = Vec::new();
 
-        for line_res in buf_reader.lines() {
+        for (i, line_res) in buf_reader.lines().enumerate() {
             match line_res {
                 Ok(line) => {
                     lines.push(line);
                 }
-                Err(e) => return Err(e),
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    return Err(Error::Parse {
+                        path: self.path.to_owned(),
+                        line: i + 1,
+                        message: "not valid UTF-8".to_owned(),
+                    })
+                }
+                Err(e) => return Err(Error::io(self.path)(e)),
             }
         }
 
+        debug!("read {} lines from {}", lines.len(), self.path.display());
         Ok(lines)
     }
 
+    /// `write` replaces the file with `lines` by writing them to a temporary
+    /// file alongside it, which is then moved over it. The new file keeps the
+    /// permissions and owner of the old one, and is synced to disk along with
+    /// the directory before this returns, so a crash leaves either the old
+    /// file or the new one. The old file is backed up first if the handle
+    /// was made with [`Handle::backed_up`].
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`Error::ReadOnly`] if the handle was made read only, or if
+    /// the temporary file can't be written or moved into place, in which
+    /// case it's removed and the file is left as it was.
     pub fn write(&self, lines: &[String]) -> Result<(), Error> {
-        let mut tmp_file_path = self.path.parent().expect("betta be hea").to_owned();
-        tmp_file_path.push(".todotuirs.tmp");
-        let mut tmp_file = File::create(&tmp_file_path)?;
+        self.writable()?;
+        let dir = self
+            .path
+            .parent()
+            .ok_or_else(|| format!("{} is not a file", self.path.display()))?;
+        let tmp_path = self.temp_path(dir);
+
+        let result = write_synced(&tmp_path, self.path, lines).and_then(|()| {
+            if let Some(backups) = self.backups {
+                // a backup which can't be taken shouldn't stop the write
+                if let Err(e) = backups.save(self.path) {
+                    warn!("not backed up: {}", e);
+                }
+            }
+            fs::rename(&tmp_path, self.path).map_err(Error::io(self.path))?;
+            File::open(dir)
+                .and_then(|d| d.sync_all())
+                .map_err(Error::io(dir))
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        result?;
 
-        for line in lines {
-            match writeln!(tmp_file, "{}", line) {
-                Ok(_) => {}
+        info!("wrote {} lines to {}", lines.len(), self.path.display());
+        Ok(())
+    }
+
+    /// `temp_path` is a name in `dir` no other write, from this process or
+    /// another, will use
+    fn temp_path(&self, dir: &Path) -> PathBuf {
+        let name = self
+            .path
+            .file_name()
+            .map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+        dir.join(format!(
+            ".{}.{}.{}.tmp",
+            name,
+            process::id(),
+            TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+}
+
+/// `write_synced` writes `lines` to a new file at `path`, with the
+/// permissions and owner of `like` if it exists, and syncs it to disk
+fn write_synced(path: &Path, like: &Path, lines: &[String]) -> Result<(), Error> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(Error::io(path))?;
+
+    if let Ok(metadata) = fs::metadata(like) {
+        file.set_permissions(metadata.permissions())
+            .map_err(Error::io(path))?;
+        // changing the owner only works as root, which is when it matters
+        let _ = fchown(&file, Some(metadata.uid()), Some(metadata.gid()));
+    }
+
+    let mut writer = BufWriter::new(file);
+    for line in lines {
+        writeln!(writer, "{}", line).map_err(Error::io(path))?;
+    }
+    let file = writer
+        .into_inner()
+        .map_err(|e| Error::io(path)(e.into_error()))?;
+    file.sync_all().map_err(Error::io(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                // TODO: remove temporary file on error
-                Err(e) => return Err(e),
-            };
+    /// `scratch` is an empty directory of its own for each test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("todotui-rs-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|&l| l.to_owned()).collect()
+    }
+
+    #[test]
+    fn save_refuses_over_changes_unless_forced() {
+        let dir = scratch("list-conflict");
+        let path = dir.join("todo.txt");
+        let done_path = dir.join("done.txt");
+        fs::write(&path, "call bob\n").unwrap();
+        let (handle, done_handle) = (Handle::new(&path), Handle::new(&done_path));
+        let mut rep = Rep::new(&handle, &done_handle).unwrap();
+
+        // something else writes the file, a second later than it was read
+        fs::write(&path, "call alice\n").unwrap();
+        let later = rep.read_at.unwrap() + Duration::from_secs(1);
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(later))
+            .unwrap();
+        rep.tasks = lines(&["call carol"]);
+        rep.modified = true;
+        assert!(matches!(
+            rep.save(&handle, &done_handle, false),
+            Err(Error::Conflict { .. })
+        ));
+        assert_eq!(handle.get_lines().unwrap(), ["call alice"]);
+
+        rep.save(&handle, &done_handle, true).unwrap();
+        assert_eq!(handle.get_lines().unwrap(), ["call carol"]);
+        assert!(!rep.modified);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn lock_waits_then_names_the_holder() {
+        let dir = scratch("list-lock");
+        let path = dir.join("todo.txt");
+        let handle = Handle::new(&path);
+        let held = handle.lock(LOCK_WAIT).unwrap();
+
+        let started = Instant::now();
+        let wait = Duration::from_millis(200);
+        match handle.lock(wait) {
+            Err(Error::Locked { pid, .. }) => assert_eq!(pid, Some(process::id())),
+            _ => panic!("locked twice"),
         }
+        assert!(started.elapsed() >= wait);
+        // the open lock is a separate one
+        handle.hold().unwrap();
 
-        fs::rename(&tmp_file_path, self.path)?;
+        drop(held);
+        handle.lock(wait).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
 
-        Ok(())
+    #[test]
+    fn write_line_needs_the_original() {
+        let dir = scratch("list-write-line");
+        let path = dir.join("todo.txt");
+        let done_path = dir.join("done.txt");
+        fs::write(&path, "call bob\ncall alice\n").unwrap();
+        let handle = Handle::new(&path);
+        let mut rep = Rep::new(&handle, &Handle::new(&done_path)).unwrap();
+
+        rep.write_line(&handle, "call alice", "call alice id:1")
+            .unwrap();
+        assert_eq!(handle.get_lines().unwrap(), ["call bob", "call alice id:1"]);
+        assert!(rep
+            .write_line(&handle, "call carol", "call carol id:2")
+            .is_err());
+        assert_eq!(handle.get_lines().unwrap(), ["call bob", "call alice id:1"]);
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -1,6 +1,13 @@
+pub mod backup;
+pub mod date;
+pub mod deps;
+pub mod edit;
 mod item;
 mod list;
+pub mod tree;
 
 pub use item::ParsedLine;
-pub use list::Rep as ListRep;
 pub use list::Handle as ListHandle;
+pub use list::LOCK_WAIT;
+pub use list::Rep as ListRep;
+pub use list::Snapshot as ListSnapshot;
@@ -0,0 +1,253 @@
+use super::{edit, ParsedLine};
+
+use std::collections::{BTreeSet, HashMap};
+
+/// `Row` is a task as it's laid out in the tree, `item` being its position in
+/// the slice the tree was built from
+pub struct Row {
+    pub item: usize,
+    pub depth: usize,
+    pub children: bool,
+    pub folded: bool,
+    /// completed and total descendants, for tasks which have any
+    pub progress: Option<(usize, usize)>,
+}
+
+/// Tree nests tasks under the task whose `id:` their `parent:` (or `p:`)
+/// refers to. Tasks whose parent isn't in the list are kept at the top level.
+pub struct Tree {
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+}
+
+impl Tree {
+    #[must_use]
+    pub fn new(items: &[ParsedLine]) -> Self {
+        let ids = ids(items);
+        let mut parents = vec![None; items.len()];
+        let mut children = vec![Vec::new(); items.len()];
+        let mut roots = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            match item.parent().and_then(|p| ids.get(p)) {
+                Some(&p) if p != i => {
+                    parents[i] = Some(p);
+                    children[p].push(i);
+                }
+                _ => roots.push(i),
+            }
+        }
+
+        let mut tree = Self {
+            parents,
+            children,
+            roots,
+        };
+
+        // tasks whose parents form a cycle can't be reached from the top
+        // level, so the first of each cycle is promoted to it
+        let mut seen = vec![false; items.len()];
+        for root in tree.roots.clone() {
+            tree.visit(root, &mut seen);
+        }
+        for i in 0..items.len() {
+            if !seen[i] {
+                tree.parents[i] = None;
+                tree.roots.push(i);
+                tree.visit(i, &mut seen);
+            }
+        }
+
+        tree
+    }
+
+    fn visit(&self, i: usize, seen: &mut [bool]) {
+        if seen[i] {
+            return;
+        }
+        seen[i] = true;
+        for &c in &self.children[i] {
+            self.visit(c, seen);
+        }
+    }
+
+    #[must_use]
+    pub fn parent(&self, i: usize) -> Option<usize> {
+        self.parents[i]
+    }
+
+    /// `previous_sibling` is the task before `i` under the same parent
+    #[must_use]
+    pub fn previous_sibling(&self, i: usize) -> Option<usize> {
+        let siblings = match self.parents[i] {
+            Some(p) => &self.children[p],
+            None => &self.roots,
+        };
+        let pos = siblings.iter().position(|&s| s == i)?;
+        pos.checked_sub(1).map(|p| siblings[p])
+    }
+
+    /// `descendants` lists every task nested under `i`, at any depth
+    #[must_use]
+    pub fn descendants(&self, i: usize) -> Vec<usize> {
+        let mut seen = vec![false; self.parents.len()];
+        seen[i] = true;
+        let mut stack = self.children[i].clone();
+        let mut descendants = Vec::new();
+        while let Some(d) = stack.pop() {
+            if !seen[d] {
+                seen[d] = true;
+                descendants.push(d);
+                stack.extend(&self.children[d]);
+            }
+        }
+        descendants
+    }
+
+    /// `rows` flattens the tree depth first, leaving out the descendants of
+    /// tasks whose id is in `folded`
+    #[must_use]
+    pub fn rows(&self, items: &[ParsedLine], folded: &BTreeSet<String>) -> Vec<Row> {
+        let mut rows = Vec::with_capacity(items.len());
+        let mut seen = vec![false; items.len()];
+        for &root in &self.roots {
+            self.push_rows(items, folded, root, 0, &mut seen, &mut rows);
+        }
+        rows
+    }
+
+    fn push_rows(
+        &self,
+        items: &[ParsedLine],
+        folded: &BTreeSet<String>,
+        i: usize,
+        depth: usize,
+        seen: &mut [bool],
+        rows: &mut Vec<Row>,
+    ) {
+        if seen[i] {
+            return;
+        }
+        seen[i] = true;
+
+        let descendants = self.descendants(i);
+        let progress = if descendants.is_empty() {
+            None
+        } else {
+            let done = descendants.iter().filter(|&&d| items[d].complete).count();
+            Some((done, descendants.len()))
+        };
+        let is_folded = items[i].id().is_some_and(|id| folded.contains(id));
+
+        rows.push(Row {
+            item: i,
+            depth,
+            children: !self.children[i].is_empty(),
+            folded: is_folded,
+            progress,
+        });
+
+        if !is_folded {
+            for &c in &self.children[i] {
+                self.push_rows(items, folded, c, depth + 1, seen, rows);
+            }
+        }
+    }
+}
+
+/// `ids` maps each task id to the position of its task in `items`
+#[must_use]
+pub fn ids<'a>(items: &[ParsedLine<'a>]) -> HashMap<&'a str, usize> {
+    items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| item.id().map(|id| (id, i)))
+        .collect()
+}
+
+/// `next_id` is one past the largest numeric `id:` in `tasks`
+#[must_use]
+pub fn next_id(tasks: &[String]) -> u64 {
+    tasks
+        .iter()
+        .filter_map(|t| edit::get_extension(t, "id")?.parse::<u64>().ok())
+        .max()
+        .map_or(1, |max| max + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(lines: &[String]) -> Vec<ParsedLine<'_>> {
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| ParsedLine::new(line, i))
+            .collect()
+    }
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|&l| l.to_owned()).collect()
+    }
+
+    /// `layout` is each row as its item and depth
+    fn layout(rows: &[Row]) -> Vec<(usize, usize)> {
+        rows.iter().map(|r| (r.item, r.depth)).collect()
+    }
+
+    #[test]
+    fn nests_under_parents() {
+        let tasks = lines(&[
+            "plan trip id:1",
+            "book hotel parent:1",
+            "x 2020-10-14 book train p:1 id:2",
+            "pack bag parent:2",
+            "water plants",
+        ]);
+        let items = parse(&tasks);
+        let tree = Tree::new(&items);
+
+        let rows = tree.rows(&items, &BTreeSet::new());
+        assert_eq!(layout(&rows), [(0, 0), (1, 1), (2, 1), (3, 2), (4, 0)]);
+        // the train is done, the hotel and bag aren't
+        assert_eq!(rows[0].progress, Some((1, 3)));
+        assert_eq!(rows[2].progress, Some((0, 1)));
+        assert_eq!(rows[1].progress, None);
+
+        assert_eq!(tree.parent(3), Some(2));
+        assert_eq!(tree.previous_sibling(2), Some(1));
+        assert_eq!(tree.previous_sibling(1), None);
+        let mut descendants = tree.descendants(0);
+        descendants.sort_unstable();
+        assert_eq!(descendants, [1, 2, 3]);
+    }
+
+    #[test]
+    fn folded_tasks_hide_their_children() {
+        let tasks = lines(&["plan trip id:1", "book hotel parent:1", "water plants"]);
+        let items = parse(&tasks);
+        let folded: BTreeSet<String> = std::iter::once("1".to_owned()).collect();
+
+        let rows = Tree::new(&items).rows(&items, &folded);
+        assert_eq!(layout(&rows), [(0, 0), (2, 0)]);
+        assert!(rows[0].folded && rows[0].children);
+    }
+
+    #[test]
+    fn missing_parents_and_cycles_go_to_the_top() {
+        let tasks = lines(&["a id:1 parent:2", "b id:2 parent:1", "c parent:9"]);
+        let items = parse(&tasks);
+        let tree = Tree::new(&items);
+
+        let rows = tree.rows(&items, &BTreeSet::new());
+        assert_eq!(layout(&rows), [(2, 0), (0, 0), (1, 1)]);
+        assert_eq!(tree.parent(0), None);
+    }
+
+    #[test]
+    fn next_id_is_past_the_largest() {
+        assert_eq!(next_id(&lines(&["a", "b id:x"])), 1);
+        assert_eq!(next_id(&lines(&["a id:3", "b id:10", "c id:x"])), 11);
+    }
+}